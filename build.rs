@@ -5,6 +5,15 @@ extern crate slog_stdlog;
 #[macro_use]
 extern crate log;
 extern crate num_traits;
+#[cfg(feature = "capi")]
+extern crate cbindgen;
+extern crate alloc;
+extern crate core;
+
+#[path = "src/dictionary/encode.rs"]
+mod encode;
+#[path = "src/keyboard/layout.rs"]
+mod layout;
 
 use std::env;
 use std::fs;
@@ -12,41 +21,76 @@ use std::io;
 use std::io::prelude::*;
 use std::io::Write;
 use std::path::Path;
+use std::str;
 use std::collections::{HashMap, HashSet};
 use num_traits::checked_pow;
 use std::cell::RefCell;
-use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use slog::DrainExt;
+use layout::{GridKeys, KeyboardStyle};
 
 
 struct WordData {
     name: String,
     data: RefCell<Vec<String>>,
+    /// Counts given for the words, see `parse_data`
+    counts: HashMap<String, u64>,
+    /// Total of the counts over the whole file
+    total: u64,
 }
 
 
-fn parse_data(data: String) -> Vec<String> {
+/// Parses a list with a word on each line, optionally followed by the number
+/// of times it was seen. Problems are added to errors with the file and line.
+fn parse_data(name: String, path: &Path, data: &[u8], errors: &mut Vec<String>) -> WordData {
     let mut word_list : Vec<String> = Vec::new();
     let mut checker: HashSet<String> = HashSet::new();
-    for line in data.lines() {
-        let mut l = line.split_whitespace();
-        let size = line.split_whitespace().count();
-        match size  {
-            1 | 2=> {
-                match l.next() {
-                    Some(frqs) => {
-                        if checker.insert(frqs.to_string()) {
-                            word_list.push(frqs.to_string());
-                        }
-                    },
-                    None => continue,
-                };  
-            },
-            _ => continue,
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut total = 0u64;
+    for (i, line) in data.split(|&b| b == b'\n').enumerate() {
+        let mut report = |problem: String| {
+            errors.push(format!("{}:{}: {}", path.display(), i + 1, problem))
+        };
+        let line = match str::from_utf8(line) {
+            Ok(line) => line,
+            Err(_) => {
+                report(String::from("not valid UTF-8"));
+                continue;
+            }
+        };
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        let count = match fields[..] {
+            [] => continue,
+            [_] => None,
+            [_, count] if count.parse::<u64>().is_ok() => count.parse::<u64>().ok(),
+            [_, count] => {
+                report(format!("count \"{}\" isn't a whole number", count.escape_debug()));
+                continue;
+            }
+            _ => {
+                report(format!("expected a word and an optional count, found {} fields",
+                               fields.len()));
+                continue;
+            }
+        };
+        let word = fields[0];
+        if word.chars().any(char::is_control) {
+            report(format!("\"{}\" contains a control character", word.escape_debug()));
+        } else if checker.insert(word.to_string()) {
+            word_list.push(word.to_string());
+            if let Some(count) = count {
+                counts.insert(word.to_string(), count);
+                total = total.saturating_add(count);
+            }
         }
     }
-    word_list
+    WordData {
+        name,
+        data: RefCell::new(word_list),
+        counts,
+        total,
+    }
 }
 
 fn is_rare_and_short(word: &String, rank: u32) -> bool {
@@ -101,53 +145,42 @@ fn main() {
     // Data files are either lists or frequency tables. Load all files in data
     // and then identify and parse accordingly and generate code
     info!("Generating source from /data/");
-    let limits : HashMap<&str, usize> = {
-        let mut map = HashMap::new();
-        map.insert("us_tv_and_film", 30000);
-        map.insert("english_wikipedia", 30000);
-        map.insert("passwords", 30000);
-        map.insert("surnames", 10000);
-        map
-    };
-
-
+    // Setting any rerun-if-changed stops cargo rerunning this script for
+    // every change to the package, so everything it reads is listed
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/dictionary/encode.rs");
     let mut exported_data : Vec<WordData> = Vec::new();
-    
-    for entry in fs::read_dir("./data").unwrap() {
-        let dir = match entry {
-            Ok(dir) => dir,
-            Err(_) => continue,
-        };
-        let path = dir.path();
-        let file_name = path.file_stem();
-        let mut file = match fs::File::open(dir.path()) {
-            Ok(file) => file,
-            Err(_) => continue,
-        };
-        let mut s = String::new();
-        
-        match file.read_to_string(&mut s) {
-            Err(_) => continue,
-            Ok(_) => { 
-                let name = match file_name.unwrap().to_str() {
-                    Some(fname) => fname,
-                    None => continue,
-                };
-                let temp = WordData{ 
-                    name: name.to_string(), 
-                    data: RefCell::new(parse_data(s)),
-                };
-                exported_data.push(temp);
-            },
-        }
+    let mut errors: Vec<String> = Vec::new();
+    read_data_dir("./data", true, &mut exported_data, &mut errors);
+    println!("cargo:rerun-if-env-changed=ZXCVBN_DATA_DIR");
+    if let Ok(dir) = env::var("ZXCVBN_DATA_DIR") {
+        info!("Generating source from {}", dir);
+        read_data_dir(&dir, false, &mut exported_data, &mut errors);
+    }
+    if !errors.is_empty() {
+        panic!("Invalid dictionary data:\n  {}", errors.join("\n  "));
     }
+    let limits = dictionary_limits();
+
     println!("Filtering data");
+    // A word tied for rank in several lists goes to the first
+    exported_data.sort_by_key(|d| list_order(&TIE_PRECEDENCE, &d.name));
     filter_data(&mut exported_data);
+    let match_order = BUILT_IN_DICTIONARIES.iter().map(|&(name, _)| name).collect::<Vec<_>>();
+    exported_data.sort_by_key(|d| list_order(&match_order, &d.name));
     println!("Applying size limits");
     // Apply limits
     for lists in exported_data.iter_mut() {
-        if let Some(limit) = limits.get(lists.name.as_str()) {
-            lists.data.borrow_mut().truncate(limit.clone());
+        let limit = limits.get(lists.name.as_str())
+                          .or_else(|| limits.get("*"))
+                          .cloned()
+                          .or_else(|| {
+                              DEFAULT_LIMITS.iter()
+                                            .find(|&&(name, _)| name == lists.name)
+                                            .map(|&(_, limit)| limit)
+                          });
+        if let Some(limit) = limit.filter(|&l| l > 0) {
+            lists.data.borrow_mut().truncate(limit);
         }
     }
     info!("Exporting frequency based data");
@@ -155,28 +188,334 @@ fn main() {
     
     let out_dir = env::var("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("frequency_data.rs");
-    let mut f = fs::File::create(&dest_path).unwrap();
+    let mut f = fs::File::create(&dest_path)
+        .unwrap_or_else(|e| panic!("Can't create {}: {}", dest_path.display(), e));
     
+    // Each dictionary is encoded compactly to a file next to the source and
+    // included as bytes, see src/dictionary.rs
     for lists in exported_data.iter() {
-        let mut line = format!("static {}: &'static [&'static str] = &[ \n", 
-                               lists.name.to_uppercase());
-        
         let data = lists.data.borrow();
+        let words = data.iter()
+                        .map(|w| (w.as_str(), lists.counts.get(w).cloned().unwrap_or(0)))
+                        .collect::<Vec<(&str, u64)>>();
+        let file_name = format!("{}.dict", lists.name);
+        let path = Path::new(&out_dir).join(&file_name);
+        fs::File::create(&path)
+            .and_then(|mut f| f.write_all(&encode::encode(&words, lists.total)))
+            .unwrap_or_else(|e| panic!("Can't write {}: {}", path.display(), e));
+        source.push_str(&format!(
+            "static {}: Dictionary = Dictionary::from_encoded(\n\
+             \tinclude_bytes!(concat!(env!(\"OUT_DIR\"), \"/{}\")));\n\n",
+            lists.name.to_uppercase(), file_name));
+    }
+    source.push_str("/// The dictionaries compiled in with the names given in matches\n\
+                     pub(crate) static DICTIONARIES: &[(&str, &Dictionary)] = &[\n");
+    for lists in exported_data.iter() {
+        let name = BUILT_IN_DICTIONARIES.iter()
+                                        .find(|&&(name, _)| name == lists.name)
+                                        .map_or(lists.name.as_str(), |&(_, name)| name);
+        source.push_str(&format!("\t({:?}, &{}),\n", name, lists.name.to_uppercase()));
+    }
+    source.push_str("];\n");
+    f.write_all(source.as_bytes())
+        .and_then(|_| f.sync_all())
+        .unwrap_or_else(|e| panic!("Can't write {}: {}", dest_path.display(), e));
+    info!("Successfully exported frequency data");
+
+    info!("Exporting keyboard layouts");
+    export_keyboard_layouts(Path::new(&out_dir));
+    export_build_year(Path::new(&out_dir));
 
-        for word in data.iter() {
-            let entry = format!("\t\"{}\",\n", word);
-            line.push_str(entry.as_str());
+    #[cfg(feature = "capi")]
+    {
+        info!("Generating C header");
+        generate_c_header(Path::new(&out_dir));
+    }
+
+    info!("Code generation finished");
+}
+
+/// Lists in ./data with the names given in matches, in the order they're
+/// matched
+const BUILT_IN_DICTIONARIES: [(&str, &str); 6] = [
+    ("female_names", "Female names"),
+    ("male_names", "Male names"),
+    ("surnames", "Surnames"),
+    ("passwords", "Passwords"),
+    ("english_wikipedia", "Wikipedia"),
+    ("us_tv_and_film", "TV and Film"),
+];
+
+/// Order lists are given words tied for rank in, which places them as
+/// zxcvbn's data does
+const TIE_PRECEDENCE: [&str; 6] = [
+    "passwords",
+    "us_tv_and_film",
+    "english_wikipedia",
+    "surnames",
+    "male_names",
+    "female_names",
+];
+
+/// Sort key putting lists in the order given, followed by the rest by name
+fn list_order(order: &[&str], name: &str) -> (usize, String) {
+    let position = order.iter().position(|&n| n == name).unwrap_or(order.len());
+    (position, name.to_string())
+}
+
+/// Number of words kept from a list unless ZXCVBN_DICTIONARY_LIMITS says
+/// otherwise. Lists not named here are kept whole.
+const DEFAULT_LIMITS: [(&str, usize); 4] = [
+    ("us_tv_and_film", 30000),
+    ("english_wikipedia", 30000),
+    ("passwords", 30000),
+    ("surnames", 10000),
+];
+
+/// Reads the lists in a directory, each named after its file. A list in ./data
+/// is only read if the dict-* feature named after it is enabled, e.g.
+/// dict-us-tv-and-film for us_tv_and_film.txt. Lists from other directories
+/// are always read, replacing any list of the same name already read. Hidden
+/// files are skipped, other files must be named so the list's name can be
+/// used in an identifier.
+fn read_data_dir(dir: &str,
+                 built_in: bool,
+                 lists: &mut Vec<WordData>,
+                 errors: &mut Vec<String>) {
+    println!("cargo:rerun-if-changed={}", dir);
+    let entries = fs::read_dir(dir).unwrap_or_else(|e| panic!("Can't read {}: {}", dir, e));
+    let mut paths = entries.filter_map(|e| e.ok())
+                           .map(|e| e.path())
+                           .filter(|p| p.is_file())
+                           .collect::<Vec<_>>();
+    paths.sort();
+    let mut names: HashMap<String, &Path> = HashMap::new();
+    for path in paths.iter() {
+        let file_name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+        if file_name.starts_with('.') {
+            continue;
+        }
+        let name = path.file_stem().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+        if !is_list_name(&name) {
+            errors.push(format!("{}: \"{}\" can't name a dictionary, names must start with a \
+                                 lowercase letter followed by lowercase letters, digits or \
+                                 underscores and not be \"dictionaries\"",
+                                path.display(), name.escape_debug()));
+            continue;
+        }
+        if let Some(other) = names.insert(name.clone(), path) {
+            errors.push(format!("{}: {} is also named \"{}\"",
+                                path.display(), other.display(), name));
+            continue;
+        }
+        if built_in && !dictionary_selected(&name) {
+            info!("Skipping {}, its feature isn't enabled", path.display());
+            continue;
+        }
+        let mut bytes = Vec::new();
+        if let Err(e) = fs::File::open(path).and_then(|mut f| f.read_to_end(&mut bytes)) {
+            errors.push(format!("{}: {}", path.display(), e));
+            continue;
+        }
+        info!("Adding dictionary {} from {}", name, path.display());
+        let data = parse_data(name, path, &bytes, errors);
+        match lists.iter_mut().find(|l| l.name == data.name) {
+            Some(list) => *list = data,
+            None => lists.push(data),
         }
-        line.push_str("];\n\n");
-        source.push_str(line.as_str());
     }
-    match f.write_all(source.as_bytes()) {
-        Ok(_) => info!("Successfully exported frequency data"),
-        Err(e) => error!("{}", e.description()),
+}
+
+/// True for names that make a valid identifier when uppercased without
+/// clashing with the other generated items
+fn is_list_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase()) &&
+        name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') &&
+        name != "dictionaries"
+}
+
+fn dictionary_selected(name: &str) -> bool {
+    let feature = format!("CARGO_FEATURE_DICT_{}", name.to_uppercase().replace('-', "_"));
+    env::var_os(feature).is_some()
+}
+
+/// Limits on the words kept from each list read from ZXCVBN_DICTIONARY_LIMITS,
+/// comma separated pairs such as "passwords=100000,surnames=0". A list named *
+/// sets the limit for every list not named, and a limit of 0 keeps the whole
+/// list.
+fn dictionary_limits() -> HashMap<String, usize> {
+    println!("cargo:rerun-if-env-changed=ZXCVBN_DICTIONARY_LIMITS");
+    let setting = env::var("ZXCVBN_DICTIONARY_LIMITS").unwrap_or_default();
+    setting.split(',')
+           .map(|pair| pair.trim())
+           .filter(|pair| !pair.is_empty())
+           .map(|pair| {
+               let mut parts = pair.splitn(2, '=');
+               let name = parts.next().unwrap_or("").trim();
+               match parts.next().map(|limit| limit.trim().parse::<usize>()) {
+                   Some(Ok(limit)) if !name.is_empty() => (name.to_string(), limit),
+                   _ => panic!("ZXCVBN_DICTIONARY_LIMITS: expected name=limit, found \"{}\"", pair),
+               }
+           })
+           .collect()
+}
+
+/// The layouts from the original zxcvbn, compiled in ahead of any layouts
+/// read from files
+const BUILT_IN_LAYOUTS: [(&str, KeyboardStyle, &str); 4] = [
+    ("qwerty", KeyboardStyle::Slanted,
+     "`~ 1! 2@ 3# 4$ 5% 6^ 7& 8* 9( 0) -_ =+\n    \
+      qQ wW eE rR tT yY uU iI oO pP [{ ]} \\|\n     \
+      aA sS dD fF gG hH jJ kK lL ;: '\"\n      \
+      zZ xX cC vV bB nN mM ,< .> /?"),
+    ("dvorak", KeyboardStyle::Slanted,
+     "`~ 1! 2@ 3# 4$ 5% 6^ 7& 8* 9( 0) [{ ]}\n    \
+      '\" ,< .> pP yY fF gG cC rR lL /? =+ \\|\n     \
+      aA oO eE uU iI dD hH tT nN sS -_\n      \
+      ;: qQ jJ kK xX bB mM wW vV zZ"),
+    ("Keypad", KeyboardStyle::Aligned,
+     "  / * -\n\
+      7 8 9 +\n\
+      4 5 6\n\
+      1 2 3\n  \
+      0 ."),
+    ("Mac keypad", KeyboardStyle::Aligned,
+     "  = / *\n\
+      7 8 9 -\n\
+      4 5 6 +\n\
+      1 2 3\n  \
+      0 ."),
+];
+
+/// Keyboard layouts are text grids, one per file, named after the graph they
+/// describe. They're read from ./keyboards and the directory in the
+/// ZXCVBN_KEYBOARD_DIR environment variable if set, skipping hidden files.
+/// Every layout, including the built in ones, is parsed here so a bad layout
+/// fails the build, and the keys found are written out for the crate to
+/// build its graphs from.
+fn export_keyboard_layouts(out_dir: &Path) {
+    let mut layouts = Vec::new();
+    for &(name, style, grid) in BUILT_IN_LAYOUTS.iter() {
+        let keys = layout::grid_keys(grid, style)
+            .unwrap_or_else(|e| panic!("Invalid built in keyboard layout {}: {}", name, e));
+        layouts.push((name.to_string(), style, keys));
     }
-    f.sync_all().unwrap();
+    let mut errors: Vec<String> = Vec::new();
 
-    info!("Code generation finished");
+    // Listed even if missing so creating it reruns the build
+    println!("cargo:rerun-if-changed=./keyboards");
+    if Path::new("./keyboards").is_dir() {
+        read_keyboard_dir("./keyboards", &mut layouts, &mut errors);
+    }
+    println!("cargo:rerun-if-env-changed=ZXCVBN_KEYBOARD_DIR");
+    if let Ok(dir) = env::var("ZXCVBN_KEYBOARD_DIR") {
+        println!("cargo:rerun-if-changed={}", dir);
+        read_keyboard_dir(&dir, &mut layouts, &mut errors);
+    }
+    if !errors.is_empty() {
+        panic!("Invalid keyboard layouts:\n  {}", errors.join("\n  "));
+    }
+
+    let mut source = String::from(
+        "static KEYBOARD_LAYOUTS: &[(&str, KeyboardStyle, &[((i32, i32), Key)])] = &[\n");
+    for &(ref name, style, ref keys) in layouts.iter() {
+        source.push_str(&format!("\t({:?}, KeyboardStyle::{:?}, &[\n", name, style));
+        for &((x, y), key) in keys.iter() {
+            source.push_str(&format!("\t\t(({}, {}), Key {{ value: {:?}, shifted: {:?} }}),\n",
+                                     x, y, key.value, key.shifted));
+        }
+        source.push_str("\t]),\n");
+    }
+    source.push_str("];\n");
+
+    let mut f = fs::File::create(out_dir.join("keyboard_layouts.rs")).unwrap();
+    f.write_all(source.as_bytes()).unwrap();
+}
+
+/// Parses the layouts in a directory, adding the errors found in any of them
+/// as file:line messages
+fn read_keyboard_dir(dir: &str,
+                     layouts: &mut Vec<(String, KeyboardStyle, GridKeys)>,
+                     errors: &mut Vec<String>) {
+    let entries = fs::read_dir(dir).unwrap_or_else(|e| panic!("Can't read {}: {}", dir, e));
+    let mut paths = entries.filter_map(|e| e.ok())
+                           .map(|e| e.path())
+                           .filter(|p| p.is_file())
+                           .collect::<Vec<_>>();
+    paths.sort();
+    for path in paths.iter() {
+        let file_name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().into_owned());
+        if file_name.starts_with('.') {
+            continue;
+        }
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let mut description = String::new();
+        fs::File::open(path).and_then(|mut f| f.read_to_string(&mut description))
+                            .unwrap_or_else(|e| panic!("Can't read {}: {}", path.display(), e));
+        match layout::description_keys(&description) {
+            Ok((style, keys)) => {
+                info!("Adding keyboard layout {}", name);
+                layouts.push((name, style, keys));
+            }
+            Err(e) => match e.line() {
+                Some(line) => errors.push(format!("{}:{}: {}", path.display(), line, e)),
+                None => errors.push(format!("{}: {}", path.display(), e)),
+            },
+        }
+    }
+}
+
+/// Writes the current year, which is the reference year for dates when the
+/// crate is built without std and so has no clock. SOURCE_DATE_EPOCH is used
+/// instead of the time if set so builds are reproducible.
+fn export_build_year(out_dir: &Path) {
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+        });
+    let year = year_from_days(seconds / 86400);
+    let mut f = fs::File::create(out_dir.join("build_year.rs")).unwrap();
+    f.write_all(year.to_string().as_bytes()).unwrap();
+}
+
+/// Gregorian year of a number of days since 1970-01-01, from Howard Hinnant's
+/// civil_from_days
+fn year_from_days(days: u64) -> u64 {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    // Years start in March, so January and February belong to the next one
+    let year = yoe + era * 400;
+    if mp >= 10 { year + 1 } else { year }
+}
+
+
+/// Writes zxcvbn.h to the output directory from the functions in src/capi.rs.
+/// The build never writes to the source tree, tests/c_header.rs checks the
+/// copy in include/ matches it.
+#[cfg(feature = "capi")]
+fn generate_c_header(out_dir: &Path) {
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let mut config = cbindgen::Config::default();
+    config.language = cbindgen::Language::C;
+    config.include_guard = Some(String::from("ZXCVBN_H"));
+    config.usize_is_size_t = true;
+    config.style = cbindgen::Style::Type;
+    config.header = Some(String::from("/* Generated by build.rs from src/capi.rs, do not edit */"));
+    config.export.rename.insert(String::from("ZxcvbnResult"), String::from("zxcvbn_result"));
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(Path::new(&crate_dir).join("src/capi.rs"))
+        .generate()
+        .expect("Unable to generate C header")
+        .write_to_file(out_dir.join("zxcvbn.h"));
 }
 
 
@@ -0,0 +1,96 @@
+/* Generated by build.rs from src/capi.rs, do not edit */
+
+#ifndef ZXCVBN_H
+#define ZXCVBN_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Result of an estimate handed to C callers. Strings are converted up front
+ * so the accessors can return pointers that live as long as the result.
+ */
+typedef struct zxcvbn_result zxcvbn_result;
+
+/**
+ * Estimates the strength of a nul terminated UTF-8 password. `user_inputs`
+ * points to `n` nul terminated strings related to the user, such as their
+ * name or email, and may be null when `n` is 0. Null entries are skipped.
+ *
+ * Returns null if the password is null or not valid UTF-8, or if the
+ * estimator panicked. The result must be released with `zxcvbn_result_free`.
+ *
+ * # Safety
+ *
+ * `password` and every non-null entry of `user_inputs` must be valid nul
+ * terminated strings and `user_inputs` must point to at least `n` pointers.
+ */
+zxcvbn_result *zxcvbn_estimate(const char *password, const char *const *user_inputs, size_t n);
+
+/**
+ * Score from 0 (too guessable) to 4 (very unguessable), or -1 for a null
+ * result
+ *
+ * # Safety
+ *
+ * `result` must be null or a pointer returned by `zxcvbn_estimate` that
+ * hasn't been freed.
+ */
+int zxcvbn_result_score(const zxcvbn_result *result);
+
+/**
+ * Order of magnitude of the guesses needed, or 0 for a null result
+ *
+ * # Safety
+ *
+ * `result` must be null or a pointer returned by `zxcvbn_estimate` that
+ * hasn't been freed.
+ */
+double zxcvbn_result_guesses_log10(const zxcvbn_result *result);
+
+/**
+ * Warning explaining what makes the password weak, empty if there is none.
+ * The string is owned by the result.
+ *
+ * # Safety
+ *
+ * `result` must be null or a pointer returned by `zxcvbn_estimate` that
+ * hasn't been freed.
+ */
+const char *zxcvbn_result_warning(const zxcvbn_result *result);
+
+/**
+ * Number of suggestions for improving the password
+ *
+ * # Safety
+ *
+ * `result` must be null or a pointer returned by `zxcvbn_estimate` that
+ * hasn't been freed.
+ */
+size_t zxcvbn_result_suggestion_count(const zxcvbn_result *result);
+
+/**
+ * Suggestion at `index`, or null if it is out of range. The string is owned
+ * by the result.
+ *
+ * # Safety
+ *
+ * `result` must be null or a pointer returned by `zxcvbn_estimate` that
+ * hasn't been freed.
+ */
+const char *zxcvbn_result_suggestion(const zxcvbn_result *result, size_t index);
+
+/**
+ * Releases a result, null is ignored
+ *
+ * # Safety
+ *
+ * `result` must be null or a pointer returned by `zxcvbn_estimate` that
+ * hasn't already been freed.
+ */
+void zxcvbn_result_free(zxcvbn_result *result);
+
+#endif /* ZXCVBN_H */
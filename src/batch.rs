@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::iter::Fuse;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use estimator::Estimator;
+use result::PasswordResult;
+
+/// Passwords queued per worker ahead of the results being read. Bounds the
+/// memory used when the input is far larger than can be held at once.
+const JOBS_PER_THREAD: usize = 4;
+
+type Job = (usize, String);
+type Outcome = (usize, thread::Result<PasswordResult>);
+
+/// Estimates for a stream of passwords computed by a pool of worker threads.
+/// The workers share one copy of the estimator and results are yielded in the
+/// order the passwords were given.
+pub struct Batch<I: Iterator> {
+    passwords: Fuse<I>,
+    /// Dropped once the passwords run out, which stops the workers
+    jobs: Option<Sender<Job>>,
+    results: Receiver<Outcome>,
+    workers: Vec<JoinHandle<()>>,
+    /// Results that finished before one for an earlier password
+    pending: BTreeMap<usize, thread::Result<PasswordResult>>,
+    /// Index of the next password to be queued
+    queued: usize,
+    /// Index of the next result to be yielded
+    yielded: usize,
+    max_queued: usize,
+}
+
+impl<I> Batch<I>
+    where I: Iterator,
+          I::Item: AsRef<str>
+{
+    /// Starts `estimator.threads` workers, or one per CPU if that is 0
+    pub fn new(estimator: &Estimator, passwords: I, user_inputs: &[&str]) -> Batch<I> {
+        let threads = match estimator.threads {
+            0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            n => n,
+        };
+        let shared = Arc::new(estimator.clone());
+        let user_inputs = Arc::new(user_inputs.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        let (jobs, job_receiver) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (result_sender, results) = mpsc::channel();
+        let mut workers = Vec::with_capacity(threads);
+        for _ in 0..threads {
+            let estimator = Arc::clone(&shared);
+            let user_inputs = Arc::clone(&user_inputs);
+            let jobs = Arc::clone(&job_receiver);
+            let results = result_sender.clone();
+            workers.push(thread::spawn(move || work(&estimator, &user_inputs, &jobs, &results)));
+        }
+        Batch {
+            passwords: passwords.fuse(),
+            jobs: Some(jobs),
+            results,
+            workers,
+            pending: BTreeMap::new(),
+            queued: 0,
+            yielded: 0,
+            max_queued: threads * JOBS_PER_THREAD,
+        }
+    }
+
+    /// Queues passwords until the workers have enough to keep busy
+    fn fill(&mut self) {
+        while self.queued - self.yielded < self.max_queued {
+            let password = match self.passwords.next() {
+                Some(password) => password.as_ref().to_string(),
+                None => {
+                    self.jobs = None;
+                    return;
+                }
+            };
+            if let Some(ref jobs) = self.jobs {
+                jobs.send((self.queued, password)).expect("estimate_many workers exited");
+            }
+            self.queued += 1;
+        }
+    }
+}
+
+impl<I> Iterator for Batch<I>
+    where I: Iterator,
+          I::Item: AsRef<str>
+{
+    type Item = PasswordResult;
+
+    /// Returns the next result in input order. A panic while estimating a
+    /// password is resumed here when its result is reached.
+    fn next(&mut self) -> Option<PasswordResult> {
+        self.fill();
+        if self.yielded == self.queued {
+            return None;
+        }
+        loop {
+            if let Some(outcome) = self.pending.remove(&self.yielded) {
+                self.yielded += 1;
+                return Some(outcome.unwrap_or_else(|e| panic::resume_unwind(e)));
+            }
+            let (index, outcome) = self.results.recv().expect("estimate_many workers exited");
+            self.pending.insert(index, outcome);
+        }
+    }
+}
+
+impl<I: Iterator> Drop for Batch<I> {
+    /// Waits for the workers to finish the passwords already queued
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(estimator: &Estimator,
+        user_inputs: &[String],
+        jobs: &Mutex<Receiver<Job>>,
+        results: &Sender<Outcome>) {
+    let user_inputs = user_inputs.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+    loop {
+        let job = match jobs.lock() {
+            Ok(jobs) => jobs.recv(),
+            Err(_) => return,
+        };
+        let (index, password) = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            estimator.estimate_with_inputs(&password, &user_inputs)
+        }));
+        if results.send((index, outcome)).is_err() {
+            return;
+        }
+    }
+}
+
+
+#[test]
+fn batch_order_test() {
+    let passwords = (0..60).map(|i| format!("Tr0ub4dor&{}", i * 7919 % 1000))
+                            .collect::<Vec<String>>();
+    let mut estimator = Estimator::new();
+    estimator.threads = 3;
+    let results = estimator.estimate_many(&passwords).collect::<Vec<_>>();
+    assert_eq!(results.len(), passwords.len());
+    for (password, result) in passwords.iter().zip(results) {
+        assert_eq!(&result.password, password);
+        assert_eq!(result.guesses, estimator.estimate(password).guesses);
+    }
+}
+
+#[test]
+fn batch_user_inputs_test() {
+    let mut estimator = Estimator::new();
+    estimator.threads = 2;
+    let results = estimator.estimate_many_with_inputs(vec!["jsmith1987", "password"], &["jsmith"])
+                           .collect::<Vec<_>>();
+    assert_eq!(results[0].guesses,
+               estimator.estimate_with_inputs("jsmith1987", &["jsmith"]).guesses);
+    assert_eq!(results[1].password, "password");
+}
+
+#[test]
+fn batch_partial_test() {
+    let estimator = Estimator::new();
+    assert_eq!(estimator.estimate_many(Vec::<String>::new()).count(), 0);
+
+    // Dropping part way through must not wait on passwords never queued
+    let mut batch = estimator.estimate_many((0..).map(|i| i.to_string()));
+    assert_eq!(batch.next().unwrap().password, "0");
+    assert_eq!(batch.next().unwrap().password, "1");
+}
@@ -0,0 +1,64 @@
+extern crate zxcvbn_rs;
+
+use zxcvbn_rs::blocklist::{BloomFilter, DEFAULT_FALSE_POSITIVE_RATE};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std::process;
+
+const USAGE: &str = "Usage: zxcvbn-blocklist <word list> <filter file> [false positive rate]";
+
+/// Calls the function with each line of the list that is valid UTF-8 and not
+/// empty, returning the number of lines that weren't valid
+fn for_each_word<F: FnMut(&str)>(path: &str, mut f: F) -> io::Result<u64> {
+    let mut invalid = 0;
+    for line in BufReader::new(File::open(path)?).split(b'\n') {
+        let mut line = line?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        match String::from_utf8(line) {
+            Ok(ref word) if word.is_empty() => {}
+            Ok(word) => f(&word),
+            Err(_) => invalid += 1,
+        }
+    }
+    Ok(invalid)
+}
+
+/// Reads the list twice, first to size the filter for the number of words
+fn build(list: &str, output: &str, rate: f64) -> io::Result<()> {
+    let mut entries = 0;
+    let invalid = for_each_word(list, |_| entries += 1)?;
+    let mut filter = BloomFilter::new(entries, rate);
+    for_each_word(list, |word| filter.insert(word))?;
+    filter.write_to(BufWriter::new(File::create(output)?))?;
+    if invalid > 0 {
+        eprintln!("Skipped {} lines that aren't valid UTF-8", invalid);
+    }
+    println!("{} words, {} bytes, false positive rate {:.6}",
+             entries,
+             filter.size(),
+             filter.false_positive_rate());
+    Ok(())
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+    let rate = match args.get(2).map(|r| r.parse::<f64>()) {
+        None => DEFAULT_FALSE_POSITIVE_RATE,
+        Some(Ok(rate)) if rate > 0.0 && rate < 1.0 => rate,
+        Some(_) => {
+            eprintln!("The false positive rate must be between 0 and 1\n{}", USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = build(&args[0], &args[1], rate) {
+        eprintln!("Failed building {} from {}: {}", args[1], args[0], e);
+        process::exit(1);
+    }
+}
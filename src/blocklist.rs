@@ -0,0 +1,259 @@
+use std::cmp;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use memmap2::Mmap;
+use matching::{BaseMatch, MatchData};
+
+/// False positive rate filters are built for unless another is given
+pub const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.001;
+
+/// Starts every filter file, the digit being the version of the layout
+const MAGIC: &[u8; 8] = b"ZXBLOOM1";
+/// The magic then the number of bits, hash functions and entries, little
+/// endian. The bits follow, the first in the low bit of the first byte
+const HEADER_LENGTH: usize = 28;
+/// Most hash functions a filter uses, reached at a false positive rate of
+/// about 1 in 4 billion
+const MAX_HASHES: u32 = 32;
+
+/// Bloom filter built from a list of words, written to a file for `Blocklist`
+/// to read. Words are lower cased, as dictionary words are.
+#[derive(Clone, Debug)]
+pub struct BloomFilter {
+    bits: Vec<u8>,
+    bit_count: u64,
+    hashes: u32,
+    entries: u64,
+}
+
+impl BloomFilter {
+    /// An empty filter sized so that once `entries` words are inserted a word
+    /// that wasn't is found with the given probability
+    pub fn new(entries: u64, false_positive_rate: f64) -> BloomFilter {
+        let n = cmp::max(entries, 1) as f64;
+        let ln2 = ::std::f64::consts::LN_2;
+        // The sizes minimising the false positives for n entries, see
+        // https://en.wikipedia.org/wiki/Bloom_filter#Optimal_number_of_hash_functions
+        let bit_count = (-n * false_positive_rate.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let hashes = (bit_count as f64 / n * ln2).round().max(1.0).min(MAX_HASHES as f64) as u32;
+        BloomFilter {
+            bits: vec![0; byte_length(bit_count)],
+            bit_count,
+            hashes,
+            entries: 0,
+        }
+    }
+
+    pub fn insert(&mut self, word: &str) {
+        for bit in positions(&word.to_lowercase(), self.bit_count, self.hashes) {
+            self.bits[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+        self.entries += 1;
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        contains(&self.bits, self.bit_count, self.hashes, word)
+    }
+
+    /// Chance a word that wasn't inserted is found, given the words inserted
+    /// so far
+    pub fn false_positive_rate(&self) -> f64 {
+        false_positive_rate(self.bit_count, self.hashes, self.entries)
+    }
+
+    /// Size of the filter in bytes, as written to a file
+    pub fn size(&self) -> u64 {
+        (HEADER_LENGTH + self.bits.len()) as u64
+    }
+
+    pub fn write_to<W: Write>(&self, mut out: W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&self.bit_count.to_le_bytes())?;
+        out.write_all(&self.hashes.to_le_bytes())?;
+        out.write_all(&self.entries.to_le_bytes())?;
+        out.write_all(&self.bits)?;
+        out.flush()
+    }
+}
+
+/// A banned password list too large to compile in, held as a memory mapped
+/// Bloom filter file written by `BloomFilter::write_to` or the
+/// `zxcvbn-blocklist` tool. Only the pages a lookup touches are read.
+///
+/// Words that were never in the list are found with the filter's
+/// `false_positive_rate`, 0.1% for filters built at the default rate. Words
+/// that were in the list are always found.
+#[derive(Clone, Debug)]
+pub struct Blocklist {
+    map: Arc<Mmap>,
+    bit_count: u64,
+    hashes: u32,
+    entries: u64,
+    /// Rank in the dictionary matches for passwords found in the list
+    pub rank: usize,
+}
+
+impl Blocklist {
+    /// Maps the filter file, failing with `InvalidData` if it isn't one. The
+    /// file must not be changed while the blocklist is in use
+    pub fn open<P: AsRef<Path>>(path: P, rank: usize) -> io::Result<Blocklist> {
+        let file = File::open(path)?;
+        // Safe as long as nothing truncates or writes the file while it's
+        // mapped, which the caller is told to ensure
+        let map = unsafe { Mmap::map(&file)? };
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        if map.len() < HEADER_LENGTH || &map[..MAGIC.len()] != MAGIC {
+            return Err(invalid("not a blocklist filter file"));
+        }
+        let bit_count = u64::from_le_bytes(read_array(&map[8..16]));
+        let hashes = u32::from_le_bytes(read_array(&map[16..20]));
+        let entries = u64::from_le_bytes(read_array(&map[20..28]));
+        if bit_count == 0 || hashes == 0 || hashes > MAX_HASHES {
+            return Err(invalid("blocklist filter header is corrupt"));
+        }
+        if map.len() - HEADER_LENGTH < byte_length(bit_count) {
+            return Err(invalid("blocklist filter file is truncated"));
+        }
+        Ok(Blocklist {
+            map: Arc::new(map),
+            bit_count,
+            hashes,
+            entries,
+            rank,
+        })
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        contains(&self.map[HEADER_LENGTH..], self.bit_count, self.hashes, word)
+    }
+
+    /// Number of words the filter was built from
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Chance a password that isn't in the list is matched anyway
+    pub fn false_positive_rate(&self) -> f64 {
+        false_positive_rate(self.bit_count, self.hashes, self.entries)
+    }
+}
+
+// u64::div_ceil needs a newer compiler than the crate supports
+#[allow(clippy::manual_div_ceil)]
+fn byte_length(bit_count: u64) -> usize {
+    ((bit_count + 7) / 8) as usize
+}
+
+fn read_array<A: Default + AsMut<[u8]>>(bytes: &[u8]) -> A {
+    let mut array = A::default();
+    array.as_mut().copy_from_slice(bytes);
+    array
+}
+
+/// FNV-1a finished with MurmurHash3's mixer, so every bit of the result
+/// depends on every byte
+fn hash(bytes: &[u8], seed: u64) -> u64 {
+    let mut h = 0xcbf2_9ce4_8422_2325 ^ seed;
+    for &b in bytes.iter() {
+        h ^= u64::from(b);
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// Bits set for a word, derived from two hashes as described by Kirsch and
+/// Mitzenmacher in "Less Hashing, Same Performance"
+fn positions(word: &str, bit_count: u64, hashes: u32) -> impl Iterator<Item = u64> {
+    let h1 = hash(word.as_bytes(), 0);
+    let h2 = hash(word.as_bytes(), 0x9e37_79b9_7f4a_7c15);
+    (0..u64::from(hashes)).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % bit_count)
+}
+
+fn contains(bits: &[u8], bit_count: u64, hashes: u32, word: &str) -> bool {
+    positions(&word.to_lowercase(), bit_count, hashes)
+        .all(|bit| bits[(bit / 8) as usize] & (1 << (bit % 8)) != 0)
+}
+
+fn false_positive_rate(bit_count: u64, hashes: u32, entries: u64) -> f64 {
+    let k = f64::from(hashes);
+    (1.0 - (-k * entries as f64 / bit_count as f64).exp()).powf(k)
+}
+
+/// Matches the whole password as a dictionary word of the blocklist's rank if
+/// it's in the list. Parts of the password aren't looked up, as each lookup
+/// risks a false positive.
+pub fn blocklist_match(password: &str, blocklist: &Blocklist) -> Vec<BaseMatch> {
+    let length = password.chars().count();
+    if length == 0 || !blocklist.contains(password) {
+        return Vec::new();
+    }
+    vec![BaseMatch {
+        pattern: String::from("Dictionary"),
+        start: 0,
+        end: length - 1,
+        token: password.to_string(),
+        data: MatchData::Dictionary {
+            matched_word: password.to_lowercase(),
+            rank: blocklist.rank,
+            dictionary_name: String::from("Blocklist"),
+            reversed: false,
+            l33t: None,
+        },
+    }]
+}
+
+
+#[test]
+fn bloom_filter_test() {
+    let mut filter = BloomFilter::new(10000, DEFAULT_FALSE_POSITIVE_RATE);
+    for i in 0..10000 {
+        filter.insert(&format!("Banned{}", i));
+    }
+    assert!((0..10000).all(|i| filter.contains(&format!("banned{}", i))));
+    assert!((filter.false_positive_rate() - DEFAULT_FALSE_POSITIVE_RATE).abs() < 0.0002);
+    let false_positives = (0..100000).filter(|i| filter.contains(&format!("allowed{}", i)))
+                                     .count();
+    assert!(false_positives > 20 && false_positives < 200, "{}", false_positives);
+}
+
+#[test]
+fn blocklist_file_test() {
+    let mut filter = BloomFilter::new(3, 0.0001);
+    for word in ["hunter2", "Tr0ub4dour&3", "ACME2024"].iter() {
+        filter.insert(word);
+    }
+    let dir = ::std::env::temp_dir().join(format!("zxcvbn-blocklist-{}", ::std::process::id()));
+    ::std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("banned.bloom");
+    filter.write_to(File::create(&path).unwrap()).unwrap();
+    assert_eq!(::std::fs::metadata(&path).unwrap().len(), filter.size());
+
+    let blocklist = Blocklist::open(&path, 10).unwrap();
+    assert_eq!(blocklist.entries(), 3);
+    assert_eq!(blocklist.false_positive_rate(), filter.false_positive_rate());
+    assert!(blocklist.contains("acme2024"));
+    assert!(!blocklist.contains("acme2025"));
+    let matches = blocklist_match("Hunter2", &blocklist);
+    assert_eq!(matches.len(), 1);
+    match matches[0].data {
+        MatchData::Dictionary { ref matched_word, rank, ref dictionary_name, .. } => {
+            assert_eq!((matched_word.as_str(), rank), ("hunter2", 10));
+            assert_eq!(dictionary_name, "Blocklist");
+        }
+        _ => panic!("Expected a dictionary match"),
+    }
+    assert!(blocklist_match("hunter3", &blocklist).is_empty());
+
+    let bytes = ::std::fs::read(&path).unwrap();
+    ::std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+    assert_eq!(Blocklist::open(&path, 10).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    ::std::fs::write(&path, "hunter2\n").unwrap();
+    assert_eq!(Blocklist::open(&path, 10).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    ::std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,264 @@
+use std::cmp;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use md4::Md4;
+use sha1::{Digest, Sha1};
+use matching::{BaseMatch, MatchData};
+
+/// Hash a set of Pwned Passwords files is keyed by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashKind {
+    /// SHA-1 of the password's UTF-8 bytes
+    Sha1,
+    /// NTLM, the MD4 of the password's UTF-16 code units in little endian
+    Ntlm,
+}
+
+impl HashKind {
+    /// The password's hash as upper case hex, as it appears in the files
+    pub fn hash(&self, password: &str) -> String {
+        let digest = match *self {
+            HashKind::Sha1 => Sha1::digest(password.as_bytes()).to_vec(),
+            HashKind::Ntlm => {
+                let bytes = password.encode_utf16()
+                                    .flat_map(|u| u.to_le_bytes())
+                                    .collect::<Vec<u8>>();
+                Md4::digest(&bytes).to_vec()
+            }
+        };
+        digest.iter().map(|b| format!("{:02X}", b)).collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Layout {
+    /// A directory of range files
+    Ranges(PathBuf),
+    /// One file of whole hashes
+    Sorted(PathBuf),
+}
+
+/// Hashes of breached passwords in the formats Pwned Passwords is downloaded
+/// in, so passwords can be checked without sending anything over the network.
+///
+/// A directory holds a range file for each 5 digit prefix of the hashes, named
+/// after the prefix such as `5BAA6.txt`. Each line is the rest of a hash and
+/// the number of times it was seen, separated by a colon, as returned by the
+/// range API. A single file instead has a line for each whole hash, sorted by
+/// hash, and is binary searched rather than read in full.
+#[derive(Clone, Debug)]
+pub struct BreachList {
+    layout: Layout,
+    kind: HashKind,
+}
+
+/// Length of the hash prefix range files are named after
+const PREFIX_LENGTH: usize = 5;
+
+impl BreachList {
+    /// Uses the range files in a directory, or a single sorted file
+    pub fn open<P: AsRef<Path>>(path: P, kind: HashKind) -> io::Result<BreachList> {
+        let path = path.as_ref().to_path_buf();
+        let layout = if path.metadata()?.is_dir() {
+            Layout::Ranges(path)
+        } else {
+            Layout::Sorted(path)
+        };
+        Ok(BreachList { layout, kind })
+    }
+
+    /// Number of times the password was seen in breaches, 0 if it never was
+    pub fn count(&self, password: &str) -> io::Result<u64> {
+        let hash = self.kind.hash(password);
+        match self.layout {
+            Layout::Ranges(ref dir) => range_count(dir, &hash),
+            Layout::Sorted(ref path) => sorted_count(path, &hash),
+        }
+    }
+}
+
+/// Splits a line into a hash, or the rest of one, and a count
+fn parse_line(line: &str) -> Option<(&str, u64)> {
+    let mut parts = line.trim_end().splitn(2, ':');
+    let hash = parts.next()?;
+    let count = parts.next()?.trim().parse::<u64>().ok()?;
+    Some((hash, count))
+}
+
+fn range_count(dir: &Path, hash: &str) -> io::Result<u64> {
+    let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+    let mut path = dir.join(format!("{}.txt", prefix));
+    if !path.exists() {
+        path = dir.join(prefix);
+    }
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if let Some((rest, count)) = parse_line(&line) {
+            if rest.eq_ignore_ascii_case(suffix) {
+                return Ok(count);
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Reads the line starting at the position, returning its length in bytes
+fn read_line(file: &mut File, position: u64, line: &mut Vec<u8>) -> io::Result<u64> {
+    file.seek(SeekFrom::Start(position))?;
+    line.clear();
+    BufReader::new(file).read_until(b'\n', line).map(|n| n as u64)
+}
+
+/// Binary searches a file of whole hashes sorted by hash
+fn sorted_count(path: &Path, hash: &str) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut line = Vec::new();
+    // The line for the hash, if there is one, starts in [low, high) and low
+    // is always the start of a line
+    let (mut low, mut high) = (0, file.metadata()?.len());
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let start = if middle == low {
+            low
+        } else {
+            // Skip to the end of the line holding the byte before the middle
+            middle - 1 + read_line(&mut file, middle - 1, &mut line)?
+        };
+        // Blank or malformed lines are stepped over
+        let mut start = start;
+        let mut found = None;
+        while start < high && found.is_none() {
+            let length = read_line(&mut file, start, &mut line)?;
+            let text = String::from_utf8_lossy(&line);
+            found = parse_line(&text).map(|(h, count)| (h.to_ascii_uppercase(), count, length));
+            if found.is_none() {
+                start += cmp::max(length, 1);
+            }
+        }
+        match found {
+            Some((found, count, length)) => {
+                match found.as_str().cmp(hash) {
+                    cmp::Ordering::Equal => return Ok(count),
+                    cmp::Ordering::Less => low = start + length,
+                    cmp::Ordering::Greater => high = start,
+                }
+            }
+            None => high = middle,
+        }
+    }
+    Ok(0)
+}
+
+/// Matches the whole password if it was seen in the breaches. Files that
+/// can't be read are treated as not holding the password.
+pub fn breach_match(password: &str, breaches: &BreachList) -> Vec<BaseMatch> {
+    let length = password.chars().count();
+    match breaches.count(password) {
+        Ok(count) if count > 0 && length > 0 => {
+            vec![BaseMatch {
+                pattern: String::from("Breach"),
+                start: 0,
+                end: length - 1,
+                token: password.to_string(),
+                data: MatchData::Breach { count },
+            }]
+        }
+        _ => Vec::new(),
+    }
+}
+
+
+#[cfg(test)]
+fn write_test_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = ::std::env::temp_dir().join(format!("zxcvbn-{}-{}", name, ::std::process::id()));
+    ::std::fs::create_dir_all(&dir).unwrap();
+    for &(file, contents) in files.iter() {
+        ::std::fs::write(dir.join(file), contents).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn hash_test() {
+    assert_eq!(HashKind::Sha1.hash("password"), "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8");
+    assert_eq!(HashKind::Ntlm.hash("password"), "8846F7EAEE8FB117AD06BDD830B7586C");
+}
+
+#[test]
+fn range_files_test() {
+    let dir = write_test_files("breach-ranges", &[
+        ("5BAA6.txt", "003D68EB55068C33ACE09247EE4C639306B:3\r\n\
+                       1E4C9B93F3F0682250B6CF8331B7EE68FD8:9659365\r\n"),
+        ("8846F", "7eaee8fb117ad06bdd830b7586c:2\n"),
+    ]);
+    let sha1 = BreachList::open(&dir, HashKind::Sha1).unwrap();
+    assert_eq!(sha1.count("password").unwrap(), 9659365);
+    assert!(sha1.count("password1").is_err());
+    let matches = breach_match("password", &sha1);
+    assert_eq!(matches.len(), 1);
+    assert_eq!((matches[0].start, matches[0].end), (0, 7));
+    assert_eq!(matches[0].data, MatchData::Breach { count: 9659365 });
+    assert!(breach_match("password1", &sha1).is_empty());
+
+    let ntlm = BreachList::open(&dir, HashKind::Ntlm).unwrap();
+    assert_eq!(ntlm.count("password").unwrap(), 2);
+    ::std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sorted_file_test() {
+    let passwords = (0..200).map(|i| format!("password{}", i)).collect::<Vec<String>>();
+    let mut lines = passwords.iter()
+                             .enumerate()
+                             .map(|(i, p)| format!("{}:{}", HashKind::Sha1.hash(p), i + 1))
+                             .collect::<Vec<String>>();
+    // Malformed lines are skipped wherever they sort
+    lines.extend(vec![String::new(), String::from("8 no count"), String::from("not a hash")]);
+    lines.sort();
+    let dir = write_test_files("breach-sorted", &[("sorted.txt", &lines.join("\r\n"))]);
+    let breaches = BreachList::open(dir.join("sorted.txt"), HashKind::Sha1).unwrap();
+    for (i, p) in passwords.iter().enumerate() {
+        assert_eq!(breaches.count(p).unwrap(), i as u64 + 1);
+    }
+    assert_eq!(breaches.count("correct horse battery staple").unwrap(), 0);
+    assert!(BreachList::open(dir.join("missing.txt"), HashKind::Sha1).is_err());
+    ::std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn breaches_test() {
+    use estimator::Estimator;
+
+    let mut lines = [("Tr0ub4dour&3", 1000000), ("correcthorse", 5)]
+        .iter()
+        .map(|&(p, count)| format!("{}:{}\n", HashKind::Sha1.hash(p), count))
+        .collect::<Vec<String>>();
+    lines.sort();
+    let dir = write_test_files("breach-estimate", &[("pwned.txt", &lines.concat())]);
+    let file = dir.join("pwned.txt");
+
+    let mut estimator = Estimator::new();
+    let before = estimator.estimate("Tr0ub4dour&3");
+    estimator.breaches = Some(BreachList::open(&file, HashKind::Sha1).unwrap());
+    let after = estimator.estimate("Tr0ub4dour&3");
+    assert_eq!(after.guesses, 1000);
+    assert!(after.guesses < before.guesses);
+    assert_eq!(after.sequence.len(), 1);
+    assert_eq!(after.sequence[0].data, MatchData::Breach { count: 1000000 });
+    assert_eq!(after.feedback.unwrap().advice, "This password has appeared in a data breach");
+    // Passwords not in the file are unaffected
+    assert_eq!(estimator.estimate("staple").guesses, Estimator::new().estimate("staple").guesses);
+
+    // Sessions look the password up again whenever it changes
+    let mut session = estimator.session();
+    session.push_str("Tr0ub4dour&3!");
+    assert!(session.estimate().guesses > 1000);
+    session.pop();
+    assert_eq!(session.estimate().guesses, 1000);
+    assert_eq!(session.estimate().guesses, 1000);
+
+    estimator.compat = true;
+    assert!(estimator.estimate("Tr0ub4dour&3").guesses > 1000);
+    ::std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,191 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic;
+use std::ptr;
+use std::slice;
+use estimator::DEFAULT_ESTIMATOR;
+
+/// Result of an estimate handed to C callers. Strings are converted up front
+/// so the accessors can return pointers that live as long as the result.
+pub struct ZxcvbnResult {
+    score: c_int,
+    guesses_log10: f64,
+    warning: CString,
+    suggestions: Vec<CString>,
+}
+
+fn c_string(s: &str) -> CString {
+    // Feedback is generated by us and never contains a nul
+    CString::new(s).unwrap_or_default()
+}
+
+/// Estimates the strength of a nul terminated UTF-8 password. `user_inputs`
+/// points to `n` nul terminated strings related to the user, such as their
+/// name or email, and may be null when `n` is 0. Null entries are skipped.
+///
+/// Returns null if the password is null or not valid UTF-8, or if the
+/// estimator panicked. The result must be released with `zxcvbn_result_free`.
+///
+/// # Safety
+///
+/// `password` and every non-null entry of `user_inputs` must be valid nul
+/// terminated strings and `user_inputs` must point to at least `n` pointers.
+#[no_mangle]
+pub unsafe extern "C" fn zxcvbn_estimate(password: *const c_char,
+                                         user_inputs: *const *const c_char,
+                                         n: usize) -> *mut ZxcvbnResult {
+    if password.is_null() || (user_inputs.is_null() && n > 0) {
+        return ptr::null_mut();
+    }
+    let password = match CStr::from_ptr(password).to_str() {
+        Ok(p) => p,
+        Err(_) => return ptr::null_mut(),
+    };
+    let inputs = if n == 0 {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(user_inputs, n)
+            .iter()
+            .filter(|p| !p.is_null())
+            .map(|&p| CStr::from_ptr(p).to_string_lossy().into_owned())
+            .collect::<Vec<String>>()
+    };
+
+    // Unwinding into C is undefined behaviour so panics are reported as null
+    let estimate = panic::catch_unwind(|| {
+        let inputs = inputs.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+        let result = DEFAULT_ESTIMATOR.estimate_with_inputs(password, &inputs);
+        let (warning, suggestions) = match result.feedback {
+            Some(ref f) => (c_string(&f.advice), f.suggestions.iter().map(|s| c_string(s)).collect()),
+            None => (c_string(""), Vec::new()),
+        };
+        ZxcvbnResult {
+            score: result.score.map_or(0, |s| s as c_int),
+            guesses_log10: result.guesses_log10,
+            warning,
+            suggestions,
+        }
+    });
+    match estimate {
+        Ok(result) => Box::into_raw(Box::new(result)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Score from 0 (too guessable) to 4 (very unguessable), or -1 for a null
+/// result
+///
+/// # Safety
+///
+/// `result` must be null or a pointer returned by `zxcvbn_estimate` that
+/// hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn zxcvbn_result_score(result: *const ZxcvbnResult) -> c_int {
+    result.as_ref().map_or(-1, |r| r.score)
+}
+
+/// Order of magnitude of the guesses needed, or 0 for a null result
+///
+/// # Safety
+///
+/// `result` must be null or a pointer returned by `zxcvbn_estimate` that
+/// hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn zxcvbn_result_guesses_log10(result: *const ZxcvbnResult) -> f64 {
+    result.as_ref().map_or(0.0, |r| r.guesses_log10)
+}
+
+/// Warning explaining what makes the password weak, empty if there is none.
+/// The string is owned by the result.
+///
+/// # Safety
+///
+/// `result` must be null or a pointer returned by `zxcvbn_estimate` that
+/// hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn zxcvbn_result_warning(result: *const ZxcvbnResult) -> *const c_char {
+    result.as_ref().map_or(ptr::null(), |r| r.warning.as_ptr())
+}
+
+/// Number of suggestions for improving the password
+///
+/// # Safety
+///
+/// `result` must be null or a pointer returned by `zxcvbn_estimate` that
+/// hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn zxcvbn_result_suggestion_count(result: *const ZxcvbnResult) -> usize {
+    result.as_ref().map_or(0, |r| r.suggestions.len())
+}
+
+/// Suggestion at `index`, or null if it is out of range. The string is owned
+/// by the result.
+///
+/// # Safety
+///
+/// `result` must be null or a pointer returned by `zxcvbn_estimate` that
+/// hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn zxcvbn_result_suggestion(result: *const ZxcvbnResult,
+                                                  index: usize) -> *const c_char {
+    result.as_ref()
+          .and_then(|r| r.suggestions.get(index))
+          .map_or(ptr::null(), |s| s.as_ptr())
+}
+
+/// Releases a result, null is ignored
+///
+/// # Safety
+///
+/// `result` must be null or a pointer returned by `zxcvbn_estimate` that
+/// hasn't already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn zxcvbn_result_free(result: *mut ZxcvbnResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+
+#[test]
+fn capi_estimate_test() {
+    let password = CString::new("jsmith1987").unwrap();
+    let input = CString::new("jsmith").unwrap();
+    let inputs = [input.as_ptr(), ptr::null()];
+    unsafe {
+        let plain = zxcvbn_estimate(password.as_ptr(), ptr::null(), 0);
+        let with_inputs = zxcvbn_estimate(password.as_ptr(), inputs.as_ptr(), 2);
+        assert!(!plain.is_null() && !with_inputs.is_null());
+        assert!(zxcvbn_result_guesses_log10(with_inputs) < zxcvbn_result_guesses_log10(plain));
+        assert!((0..5).contains(&zxcvbn_result_score(plain)));
+
+        let count = zxcvbn_result_suggestion_count(with_inputs);
+        for i in 0..count {
+            assert!(!zxcvbn_result_suggestion(with_inputs, i).is_null());
+        }
+        assert!(zxcvbn_result_suggestion(with_inputs, count).is_null());
+        assert!(!zxcvbn_result_warning(with_inputs).is_null());
+        zxcvbn_result_free(plain);
+        zxcvbn_result_free(with_inputs);
+
+        let common = CString::new("password").unwrap();
+        let result = zxcvbn_estimate(common.as_ptr(), ptr::null(), 0);
+        assert_eq!(zxcvbn_result_score(result), 0);
+        assert_eq!(CStr::from_ptr(zxcvbn_result_warning(result)).to_str().unwrap(),
+                   "This is a top-10 common password");
+        zxcvbn_result_free(result);
+    }
+}
+
+#[test]
+fn capi_invalid_input_test() {
+    let invalid = [0xffu8, 0xfe, 0];
+    unsafe {
+        assert!(zxcvbn_estimate(ptr::null(), ptr::null(), 0).is_null());
+        assert!(zxcvbn_estimate(invalid.as_ptr() as *const c_char, ptr::null(), 0).is_null());
+        let password = CString::new("password").unwrap();
+        assert!(zxcvbn_estimate(password.as_ptr(), ptr::null(), 1).is_null());
+        assert_eq!(zxcvbn_result_score(ptr::null()), -1);
+        zxcvbn_result_free(ptr::null_mut());
+    }
+}
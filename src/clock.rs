@@ -0,0 +1,110 @@
+use core::fmt;
+#[cfg(test)]
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+#[cfg(all(feature = "std", not(all(feature = "wasm", target_arch = "wasm32"))))]
+use std::time::Instant;
+#[cfg(all(feature = "std", not(all(feature = "wasm", target_arch = "wasm32"))))]
+use chrono::{Datelike, Local};
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+use js_sys::Date;
+
+/// Year the crate was built, or the year of SOURCE_DATE_EPOCH if it was set
+pub const BUILD_YEAR: i32 = include!(concat!(env!("OUT_DIR"), "/build_year.rs"));
+
+/// Source of the time used for `Estimator::time_budget` and of the year dates
+/// and recent years are compared against
+pub trait Clock: fmt::Debug + Sync {
+    /// Time since some fixed point, only the difference between two readings
+    /// is used
+    fn now(&self) -> Duration;
+
+    fn current_year(&self) -> i32;
+}
+
+/// The system clock. std has no clock in the browser so the wasm build asks
+/// JavaScript for the time instead.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+#[cfg(all(feature = "std", not(all(feature = "wasm", target_arch = "wasm32"))))]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        lazy_static! {
+            static ref EPOCH: Instant = Instant::now();
+        }
+        EPOCH.elapsed()
+    }
+
+    fn current_year(&self) -> i32 {
+        Local::now().year()
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        Duration::from_millis(Date::now().max(0.0) as u64)
+    }
+
+    fn current_year(&self) -> i32 {
+        Date::new_0().get_full_year() as i32
+    }
+}
+
+/// A clock that never advances and is always in the same year. Time budgets
+/// never run out with it, so it's only suitable where they aren't used.
+#[derive(Clone, Copy, Debug)]
+pub struct FixedClock {
+    pub year: i32,
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> Duration {
+        Duration::from_secs(0)
+    }
+
+    fn current_year(&self) -> i32 {
+        self.year
+    }
+}
+
+/// Clock which moves on a millisecond every time it's read, so tests can
+/// place a deadline between readings and count them
+#[cfg(test)]
+#[derive(Debug)]
+pub(crate) struct SteppingClock(AtomicU64);
+
+#[cfg(test)]
+impl SteppingClock {
+    pub const fn new() -> SteppingClock {
+        SteppingClock(AtomicU64::new(0))
+    }
+
+    /// Number of times the clock has been read
+    pub fn readings(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+impl Clock for SteppingClock {
+    fn now(&self) -> Duration {
+        Duration::from_millis(self.0.fetch_add(1, Ordering::SeqCst))
+    }
+
+    fn current_year(&self) -> i32 {
+        2017
+    }
+}
+
+/// Clock used by `Estimator::default`. Without the `std` feature there is no
+/// system clock, so this is a `FixedClock` in the build year and embedded
+/// users should set `Estimator::clock` to one reading their own timer.
+#[cfg(feature = "std")]
+pub static DEFAULT_CLOCK: &dyn Clock = &SystemClock;
+#[cfg(not(feature = "std"))]
+pub static DEFAULT_CLOCK: &dyn Clock = &FixedClock { year: BUILD_YEAR };
@@ -0,0 +1,271 @@
+use alloc::borrow::Cow;
+use core::cmp::Ordering;
+use core::fmt;
+use prelude::*;
+
+mod encode;
+
+/// Bytes before the block offsets
+const HEADER_LENGTH: usize = 20;
+
+/// A list of words ranked from most to least common, stored compactly as the
+/// sorted words front coded in blocks (see `encode::encode`). Ranks are found
+/// with a binary search over the blocks. Words may also have the number of
+/// times they were seen in the data the list came from.
+#[derive(Clone)]
+pub struct Dictionary {
+    data: Cow<'static, [u8]>,
+}
+
+impl Dictionary {
+    /// Encodes words given most common first
+    pub fn new(words: &[&str]) -> Dictionary {
+        let words = words.iter().map(|&w| (w, 0)).collect::<Vec<(&str, u64)>>();
+        Dictionary { data: Cow::Owned(encode::encode(&words, 0)) }
+    }
+
+    /// Encodes words given most common first with the number of times each
+    /// was seen, 0 if unknown
+    pub fn with_counts(words: &[(&str, u64)]) -> Dictionary {
+        let total = words.iter().fold(0u64, |total, w| total.saturating_add(w.1));
+        Dictionary { data: Cow::Owned(encode::encode(words, total)) }
+    }
+
+    /// Wraps data encoded by the build script
+    pub const fn from_encoded(data: &'static [u8]) -> Dictionary {
+        Dictionary { data: Cow::Borrowed(data) }
+    }
+
+    /// Number of words
+    pub fn len(&self) -> usize {
+        read_u32(&self.data, 0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Length in chars of the longest word
+    pub fn longest(&self) -> usize {
+        read_u32(&self.data, 4)
+    }
+
+    /// Total of the counts in the data the words were taken from, which may
+    /// have held more words than were kept. 0 if there are no counts
+    pub fn total(&self) -> u64 {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&self.data[12..20]);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Rank of the word, 1 for the most common, or None if it isn't in the
+    /// dictionary
+    pub fn rank(&self, word: &str) -> Option<usize> {
+        self.find(word).map(|(rank, _)| rank)
+    }
+
+    /// Number of times the word was seen, or None if it isn't in the
+    /// dictionary or has no count
+    pub fn count(&self, word: &str) -> Option<u64> {
+        self.find(word).and_then(|(_, count)| if count > 0 { Some(count) } else { None })
+    }
+
+    /// The counts of the words that have them, largest first
+    pub fn frequencies(&self) -> Vec<u64> {
+        let mut position = self.block_start(0);
+        let mut word = Vec::new();
+        let mut counts = Vec::new();
+        while position < self.data.len() {
+            let (_, count) = read_entry(&self.data, &mut position, &mut word);
+            if count > 0 {
+                counts.push(count);
+            }
+        }
+        counts.sort_by(|a, b| b.cmp(a));
+        counts
+    }
+
+    /// Rank and count of a word
+    fn find(&self, word: &str) -> Option<(usize, u64)> {
+        let word = word.as_bytes();
+        // Find the last block starting at or before the word
+        let (mut low, mut high) = (0, self.blocks());
+        while low < high {
+            let middle = (low + high) / 2;
+            if self.first_word(middle) <= word {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        if low == 0 {
+            return None;
+        }
+        let block = low - 1;
+        let end = if block + 1 < self.blocks() {
+            self.block_start(block + 1)
+        } else {
+            self.data.len()
+        };
+        let mut position = self.block_start(block);
+        let mut current = Vec::new();
+        while position < end {
+            let entry = read_entry(&self.data, &mut position, &mut current);
+            match current[..].cmp(word) {
+                Ordering::Less => {}
+                Ordering::Equal => return Some(entry),
+                Ordering::Greater => return None,
+            }
+        }
+        None
+    }
+
+    /// The words in byte order with their ranks
+    pub fn words(&self) -> Words<'_> {
+        Words {
+            data: &self.data[..],
+            position: self.block_start(0),
+            word: Vec::new(),
+        }
+    }
+
+    /// The words most common first
+    pub fn by_rank(&self) -> Vec<String> {
+        let mut words = self.words().collect::<Vec<(String, usize)>>();
+        words.sort_by_key(|w| w.1);
+        words.into_iter().map(|w| w.0).collect()
+    }
+
+    fn blocks(&self) -> usize {
+        read_u32(&self.data, 8)
+    }
+
+    fn block_start(&self, block: usize) -> usize {
+        let offsets_end = HEADER_LENGTH + 4 * self.blocks();
+        if block < self.blocks() {
+            offsets_end + read_u32(&self.data, HEADER_LENGTH + 4 * block)
+        } else {
+            offsets_end
+        }
+    }
+
+    /// First word of a block, which is stored whole
+    fn first_word(&self, block: usize) -> &[u8] {
+        let mut position = self.block_start(block);
+        read_varint(&self.data, &mut position);
+        let length = read_varint(&self.data, &mut position) as usize;
+        &self.data[position..position + length]
+    }
+}
+
+impl fmt::Debug for Dictionary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Dictionary {{ words: {}, bytes: {} }}", self.len(), self.data.len())
+    }
+}
+
+/// Iterator over the words of a dictionary, see `Dictionary::words`
+pub struct Words<'a> {
+    data: &'a [u8],
+    position: usize,
+    word: Vec<u8>,
+}
+
+impl<'a> Iterator for Words<'a> {
+    type Item = (String, usize);
+
+    fn next(&mut self) -> Option<(String, usize)> {
+        if self.position >= self.data.len() {
+            return None;
+        }
+        let (rank, _) = read_entry(self.data, &mut self.position, &mut self.word);
+        Some((String::from_utf8_lossy(&self.word).into_owned(), rank))
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> usize {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[at..at + 4]);
+    u32::from_le_bytes(bytes) as usize
+}
+
+fn read_varint(data: &[u8], position: &mut usize) -> u64 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*position];
+        *position += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+/// Reads the word at the position over the end of the word before it, moving
+/// the position to the next word. Returns the word's rank and count.
+fn read_entry(data: &[u8], position: &mut usize, word: &mut Vec<u8>) -> (usize, u64) {
+    let shared = read_varint(data, position) as usize;
+    let length = read_varint(data, position) as usize;
+    word.truncate(shared);
+    word.extend_from_slice(&data[*position..*position + length]);
+    *position += length;
+    let rank = read_varint(data, position) as usize;
+    (rank, read_varint(data, position))
+}
+
+
+#[test]
+fn dictionary_test() {
+    let words = ["password", "passw0rd", "pass", "dragon", "pass", "drag", "ünïcödé"];
+    let dictionary = Dictionary::new(&words);
+    assert_eq!(dictionary.len(), 6);
+    assert_eq!(dictionary.longest(), 8);
+    assert_eq!(dictionary.rank("password"), Some(1));
+    assert_eq!(dictionary.rank("pass"), Some(3));
+    assert_eq!(dictionary.rank("drag"), Some(6));
+    assert_eq!(dictionary.rank("ünïcödé"), Some(7));
+    assert_eq!(dictionary.rank("passwor"), None);
+    assert_eq!(dictionary.rank("a"), None);
+    assert_eq!(dictionary.rank("zzz"), None);
+    assert_eq!(dictionary.by_rank(),
+               vec!["password", "passw0rd", "pass", "dragon", "drag", "ünïcödé"]);
+
+    assert_eq!(dictionary.total(), 0);
+    assert_eq!(dictionary.count("password"), None);
+    assert!(dictionary.frequencies().is_empty());
+
+    let empty = Dictionary::new(&[]);
+    assert!(empty.is_empty());
+    assert_eq!(empty.rank("a"), None);
+    assert_eq!(empty.words().count(), 0);
+}
+
+#[test]
+fn dictionary_blocks_test() {
+    // Enough words for several blocks, with ranks that need multibyte varints
+    let words = (0..1000).map(|i| format!("word{}", i)).collect::<Vec<String>>();
+    let words = words.iter().map(|w| w.as_str()).collect::<Vec<&str>>();
+    let dictionary = Dictionary::new(&words);
+    for (i, word) in words.iter().enumerate() {
+        assert_eq!(dictionary.rank(word), Some(i + 1));
+    }
+    assert_eq!(dictionary.rank("word1000"), None);
+    assert_eq!(dictionary.by_rank(), words);
+}
+
+#[test]
+fn dictionary_counts_test() {
+    let dictionary = Dictionary::with_counts(&[("123456", 55893), ("password", 20785),
+                                               ("dragon", 0), ("123456", 7),
+                                               ("qwerty", 300000000000)]);
+    assert_eq!(dictionary.len(), 4);
+    assert_eq!(dictionary.total(), 55893 + 20785 + 7 + 300000000000);
+    assert_eq!(dictionary.count("password"), Some(20785));
+    assert_eq!(dictionary.count("123456"), Some(55893));
+    assert_eq!(dictionary.count("dragon"), None);
+    assert_eq!(dictionary.rank("dragon"), Some(3));
+    assert_eq!(dictionary.rank("qwerty"), Some(5));
+    assert_eq!(dictionary.frequencies(), vec![300000000000, 55893, 20785]);
+}
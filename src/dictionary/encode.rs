@@ -0,0 +1,77 @@
+// Encoding of `Dictionary` data. The build script includes this file to encode
+// the built in dictionaries, so it only uses alloc.
+
+use alloc::vec::Vec;
+
+/// Words in each block. The first word of a block is stored whole so lookups
+/// can binary search the blocks, the rest only store what differs from the
+/// word before.
+const BLOCK_SIZE: usize = 16;
+
+fn push_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+/// Writes the value 7 bits at a time, low bits first, with the top bit set on
+/// every byte but the last
+fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Encodes words given most common first, so a word's rank is its position
+/// counting from 1, with the number of times each was seen or 0 if unknown.
+/// Repeats of a word are dropped. The total is of the counts in the list the
+/// words were taken from.
+///
+/// The data starts with the number of words, the length in chars of the
+/// longest word and the number of blocks as little endian u32s, and the total
+/// as a little endian u64. Then comes the offset of each block from the end of
+/// the offsets as a u32. Blocks hold the words in byte order, each as varints
+/// of the bytes it shares with the word before and of the remaining bytes,
+/// followed by those bytes and varints of its rank and count.
+pub fn encode(words: &[(&str, u64)], total: u64) -> Vec<u8> {
+    let mut ranked = words.iter()
+                          .enumerate()
+                          .map(|(i, &(w, count))| (w, i + 1, count))
+                          .collect::<Vec<(&str, usize, u64)>>();
+    // Repeats sort after the word's first rank
+    ranked.sort();
+    ranked.dedup_by_key(|w| w.0);
+
+    let mut offsets = Vec::new();
+    let mut blocks = Vec::new();
+    for (i, &(word, rank, count)) in ranked.iter().enumerate() {
+        let shared = if i % BLOCK_SIZE == 0 {
+            offsets.push(blocks.len());
+            0
+        } else {
+            let previous = ranked[i - 1].0.as_bytes();
+            previous.iter().zip(word.as_bytes()).take_while(|&(a, b)| a == b).count()
+        };
+        push_varint(&mut blocks, shared as u64);
+        push_varint(&mut blocks, (word.len() - shared) as u64);
+        blocks.extend_from_slice(&word.as_bytes()[shared..]);
+        push_varint(&mut blocks, rank as u64);
+        push_varint(&mut blocks, count);
+    }
+
+    let longest = ranked.iter().map(|w| w.0.chars().count()).max().unwrap_or(0);
+    let mut data = Vec::with_capacity(20 + 4 * offsets.len() + blocks.len());
+    push_u32(&mut data, ranked.len());
+    push_u32(&mut data, longest);
+    push_u32(&mut data, offsets.len());
+    data.extend_from_slice(&total.to_le_bytes());
+    for &offset in offsets.iter() {
+        push_u32(&mut data, offset);
+    }
+    data.extend_from_slice(&blocks);
+    data
+}
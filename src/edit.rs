@@ -0,0 +1,141 @@
+use rand::{CryptoRng, Rng};
+use estimator::Estimator;
+use generator::Generator;
+use matching::{BaseMatch, MatchData};
+#[cfg(test)]
+use matching::DICTIONARIES;
+use prelude::*;
+use result::PasswordScore;
+
+/// Characters a key of a keyboard walk is replaced with
+const WALK_BREAKERS: &str = "abcdefghijklmnopqrstuvwxyz0123456789!#$%&*+?@";
+
+/// Kind of change an edit makes to the password
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditKind {
+    /// A random word added to the end
+    AppendWord,
+    /// The middle key of a keyboard walk replaced with a random character
+    BreakKeyboardWalk,
+    /// A recent year or date replaced with random digits
+    ReplaceYear,
+}
+
+/// A change to the user's password and how it would be scored
+#[derive(Clone, Debug)]
+pub struct Edit {
+    pub kind: EditKind,
+    /// The password after the edit
+    pub password: String,
+    /// Message describing the edit to the user
+    pub description: String,
+    pub score: PasswordScore,
+    pub guesses_log10: f64,
+}
+
+/// Proposes an edit for each pattern in the best match sequence that can be
+/// broken up, and adding a random word, most effective first. The word comes
+/// from the generator's default list, so isn't proposed when no built in
+/// dictionary is compiled in.
+pub(crate) fn suggest_edits<R>(estimator: &Estimator,
+                               password: &str,
+                               user_inputs: &[&str],
+                               rng: &mut R) -> Vec<Edit>
+    where R: Rng + CryptoRng + ?Sized
+{
+    let generator = Generator::new();
+    let words = generator.word_list();
+    let result = estimator.estimate_with_inputs(password, user_inputs);
+    let chars = password.chars().collect::<Vec<char>>();
+
+    let mut candidates = Vec::new();
+    if !words.is_empty() {
+        let word = &words[rng.gen_range(0..words.len())];
+        candidates.push((EditKind::AppendWord,
+                         format!("{}{}", password, word),
+                         format!("Add another word, such as \"{}\"", word)));
+    }
+    for m in result.sequence.iter() {
+        match m.data {
+            MatchData::Spatial { .. } => {
+                let middle = m.start + m.token.chars().count() / 2;
+                let breakers = WALK_BREAKERS.chars()
+                                            .filter(|c| !m.token.contains(*c))
+                                            .collect::<Vec<char>>();
+                let mut edited = chars.clone();
+                edited[middle] = breakers[rng.gen_range(0..breakers.len())];
+                candidates.push((EditKind::BreakKeyboardWalk,
+                                 edited.into_iter().collect(),
+                                 format!("Break up the keyboard pattern \"{}\"", m.token)));
+            }
+            MatchData::Regex { ref name } if name == "recent year" => {
+                candidates.push((EditKind::ReplaceYear,
+                                 replace_digits(&chars, m, rng),
+                                 format!("Replace the year \"{}\" with other digits", m.token)));
+            }
+            MatchData::Date { .. } => {
+                candidates.push((EditKind::ReplaceYear,
+                                 replace_digits(&chars, m, rng),
+                                 format!("Replace the date \"{}\" with other digits", m.token)));
+            }
+            _ => {}
+        }
+    }
+
+    let mut edits = candidates.into_iter()
+                              .map(|(kind, password, description)| {
+                                  let result = estimator.estimate_with_inputs(&password,
+                                                                              user_inputs);
+                                  Edit {
+                                      kind,
+                                      password,
+                                      description,
+                                      score: result.score.unwrap_or(PasswordScore::VeryWeak),
+                                      guesses_log10: result.guesses_log10,
+                                  }
+                              })
+                              .collect::<Vec<Edit>>();
+    edits.sort_by(|a, b| b.guesses_log10.total_cmp(&a.guesses_log10));
+    edits
+}
+
+/// Replaces the digits in a match with random ones. Each run of digits starts
+/// with one other than 1 or 2 so it doesn't read as another year
+fn replace_digits<R>(chars: &[char], m: &BaseMatch, rng: &mut R) -> String
+    where R: Rng + ?Sized
+{
+    let mut edited = chars.to_vec();
+    let mut in_run = false;
+    for c in edited[m.start..m.end + 1].iter_mut() {
+        if c.is_ascii_digit() {
+            let low = if in_run { 0 } else { 3 };
+            *c = char::from(b'0' + rng.gen_range(low..10u8));
+        }
+        in_run = c.is_ascii_digit();
+    }
+    edited.into_iter().collect()
+}
+
+
+#[test]
+fn suggest_edits_test() {
+    let estimator = Estimator::new();
+    let edits = estimator.suggest_edits("dfghjk1990", &[]);
+    let kinds = edits.iter().map(|e| e.kind).collect::<Vec<EditKind>>();
+    assert_eq!(kinds.contains(&EditKind::AppendWord), !DICTIONARIES.is_empty());
+    assert!(kinds.contains(&EditKind::BreakKeyboardWalk));
+    assert!(kinds.contains(&EditKind::ReplaceYear));
+    for pair in edits.windows(2) {
+        assert!(pair[0].guesses_log10 >= pair[1].guesses_log10);
+    }
+    for edit in edits.iter() {
+        assert!(edit.password != "dfghjk1990");
+        assert_eq!(edit.password.chars().count() > 10, edit.kind == EditKind::AppendWord);
+        let result = estimator.estimate(&edit.password);
+        assert_eq!(result.score, Some(edit.score));
+        assert_eq!(result.guesses_log10, edit.guesses_log10);
+    }
+    let year = edits.iter().find(|e| e.kind == EditKind::ReplaceYear).unwrap();
+    assert!(year.password.starts_with("dfghjk"));
+    assert!(!year.password[6..].starts_with('1') && !year.password[6..].starts_with('2'));
+}
@@ -0,0 +1,244 @@
+use core::cmp;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use batch::Batch;
+#[cfg(feature = "blocklist")]
+use blocklist::Blocklist;
+#[cfg(feature = "breach")]
+use breach::BreachList;
+use clock::{Clock, DEFAULT_CLOCK};
+use edit::{self, Edit};
+use keyboard::{self, Keyboard, KeyboardStyle, LayoutError, DEFAULT_KEYBOARDS};
+use matching::{self, BaseMatch, MatchContext, DEFAULT_L33T_BUDGET, DEFAULT_L33T_TABLE};
+use prelude::*;
+use result::PasswordResult;
+use scoring;
+use session::Session;
+use rand::{CryptoRng, Rng};
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+
+/// Default number of characters analysed by the matchers, anything after
+/// this is scored as bruteforce
+pub const DEFAULT_MAX_LENGTH: usize = 100;
+
+/// Guesses reported for a password when the time budget runs out, low enough
+/// to score it as `Weak`
+const TRUNCATED_GUESSES: u64 = 1_000_000;
+
+lazy_static! {
+    pub(crate) static ref DEFAULT_ESTIMATOR: Estimator = Estimator::default();
+}
+
+/// Password strength estimator. Holds the configuration shared by the
+/// matchers so it can be built once and reused for every password.
+#[derive(Clone, Debug)]
+pub struct Estimator {
+    /// Keyboard graphs searched for spatial patterns, paired with the name
+    /// reported in matches
+    pub keyboards: Vec<(String, Keyboard)>,
+    /// l33t substitutions tried by the l33t matcher. Keys are the l33t tokens
+    /// and values the letters each can stand for
+    pub l33t_table: HashMap<String, String>,
+    /// Maximum number of l33t substituted variants of a password checked
+    /// against the dictionaries
+    pub l33t_budget: usize,
+    /// Repeated or skipped keys allowed in each keyboard walk, such as the
+    /// second w in "qwwerty" or the missing e in "qwrty". Each is priced into
+    /// the walk's guesses.
+    pub spatial_slips: usize,
+    /// Number of characters passed to the matchers. Matching and scoring are
+    /// quadratic in the length so longer input is cut here and the rest scored
+    /// as bruteforce
+    pub max_length: usize,
+    /// Wall-clock time allowed for matching. Once it passes the remaining
+    /// matchers, l33t variants and repeats are skipped and the result is
+    /// marked truncated. Scoring the matches found isn't interrupted, its cost
+    /// is bounded by `max_length`.
+    pub time_budget: Option<Duration>,
+    /// Clock measuring `time_budget` and giving the year dates are compared
+    /// against. See `clock::DEFAULT_CLOCK` for builds without std.
+    pub clock: &'static dyn Clock,
+    /// Records how the guesses were computed in
+    /// `PasswordResult::explanation`
+    pub explain: bool,
+    /// Number of the least guessable match sequences recorded in
+    /// `PasswordResult::alternatives`
+    pub alternatives: usize,
+    /// Worker threads started by `estimate_many`, 0 starts one per CPU
+    pub threads: usize,
+    /// Matches and scores passwords the way dropbox's zxcvbn 4.4 does, so the
+    /// guesses and scores agree with it. Ignored with `compat`: `l33t_table`,
+    /// `l33t_budget`, `spatial_slips`, `cumulative_frequency`, `breaches` and
+    /// `blocklist`. `keyboards` and the built in dictionaries are still used,
+    /// so scores only agree while they're the defaults: every dictionary
+    /// feature on and neither ZXCVBN_DATA_DIR nor ZXCVBN_DICTIONARY_LIMITS set
+    /// when building. Spatial guesses are rounded, and zxcvbn's own guesses
+    /// lose precision above 2^53, so large guesses may differ slightly.
+    pub compat: bool,
+    /// Prices a dictionary word by the guesses to reach it when the words of
+    /// every dictionary with counts are tried together, most frequent first,
+    /// rather than by its rank in its own dictionary. Crackers merge lists
+    /// this way, so a word far down a large list isn't scored as if only that
+    /// list were tried. Words without counts keep their rank.
+    pub cumulative_frequency: bool,
+    /// Breached password files the whole password is looked up in. A
+    /// password found there is priced by how often it was seen.
+    #[cfg(feature = "breach")]
+    pub breaches: Option<BreachList>,
+    /// Banned passwords held in a Bloom filter file. A password in the list
+    /// is matched as a dictionary word of the blocklist's rank, as are a
+    /// small fraction of those that aren't, see `Blocklist`.
+    #[cfg(feature = "blocklist")]
+    pub blocklist: Option<Blocklist>,
+}
+
+impl Default for Estimator {
+    fn default() -> Estimator {
+        Estimator {
+            keyboards: DEFAULT_KEYBOARDS.clone(),
+            l33t_table: DEFAULT_L33T_TABLE.clone(),
+            l33t_budget: DEFAULT_L33T_BUDGET,
+            spatial_slips: 0,
+            max_length: DEFAULT_MAX_LENGTH,
+            time_budget: None,
+            clock: DEFAULT_CLOCK,
+            explain: false,
+            alternatives: 0,
+            threads: 0,
+            compat: false,
+            cumulative_frequency: false,
+            #[cfg(feature = "breach")]
+            breaches: None,
+            #[cfg(feature = "blocklist")]
+            blocklist: None,
+        }
+    }
+}
+
+impl Estimator {
+    pub fn new() -> Estimator {
+        Default::default()
+    }
+
+    /// Adds a keyboard described by a text grid (see `keyboard::parse_layout`)
+    /// to the graphs used for spatial matching
+    pub fn add_keyboard(&mut self,
+                        name: &str,
+                        layout: &str,
+                        style: KeyboardStyle) -> Result<(), LayoutError> {
+        let graph = keyboard::parse_layout(layout, style)?;
+        self.keyboards.push((name.to_string(), graph));
+        Ok(())
+    }
+
+    /// Matches the password against every matcher returning the matches
+    pub fn omnimatch(&self, password: &str) -> Vec<BaseMatch> {
+        matching::omnimatch_with(password, self, &MatchContext::new(self))
+    }
+
+    /// Finds the most guessable match sequence for the password and the
+    /// feedback for it
+    pub fn estimate(&self, password: &str) -> PasswordResult {
+        self.estimate_with_inputs(password, &[])
+    }
+
+    /// Estimates the password also matching it against words related to the
+    /// user, such as their name or email address, which are easy guesses for
+    /// an attacker who knows them
+    pub fn estimate_with_inputs(&self, password: &str, user_inputs: &[&str]) -> PasswordResult {
+        let start = self.clock.now();
+        let context = MatchContext::with_user_inputs(self, user_inputs);
+        let analysed = matching::char_slice(password, 0, self.max_length);
+        let matches = matching::omnimatch_with(analysed, self, &context);
+        let result = scoring::most_guessable_match_sequence_in(analysed.to_string(),
+                                                               &matches,
+                                                               context.scoring());
+        self.finish(result, &matches, password, &context, start)
+    }
+
+    /// Estimates every password on a pool of `threads` workers, yielding the
+    /// results in the same order as the passwords. Passwords are read from
+    /// the iterator as the workers need them, so it can be arbitrarily long.
+    #[cfg(feature = "std")]
+    pub fn estimate_many<I>(&self, passwords: I) -> Batch<I::IntoIter>
+        where I: IntoIterator,
+              I::Item: AsRef<str>
+    {
+        Batch::new(self, passwords.into_iter(), &[])
+    }
+
+    /// `estimate_many` with the same user inputs for every password
+    #[cfg(feature = "std")]
+    pub fn estimate_many_with_inputs<I>(&self,
+                                        passwords: I,
+                                        user_inputs: &[&str]) -> Batch<I::IntoIter>
+        where I: IntoIterator,
+              I::Item: AsRef<str>
+    {
+        Batch::new(self, passwords.into_iter(), user_inputs)
+    }
+
+    /// Proposes edits to the password that make it harder to guess, such as
+    /// adding a word or breaking up a keyboard walk, with the score each would
+    /// get. The edits are random, drawn from the operating system's random
+    /// number generator. Adding a word needs a built in dictionary compiled in
+    #[cfg(feature = "std")]
+    pub fn suggest_edits(&self, password: &str, user_inputs: &[&str]) -> Vec<Edit> {
+        edit::suggest_edits(self, password, user_inputs, &mut OsRng)
+    }
+
+    /// `suggest_edits` using the given cryptographically secure random number
+    /// generator
+    pub fn suggest_edits_with_rng<R>(&self,
+                                     password: &str,
+                                     user_inputs: &[&str],
+                                     rng: &mut R) -> Vec<Edit>
+        where R: Rng + CryptoRng + ?Sized
+    {
+        edit::suggest_edits(self, password, user_inputs, rng)
+    }
+
+    /// Starts an incremental session for scoring a password as it is typed
+    pub fn session(&self) -> Session<'_> {
+        Session::new(self)
+    }
+
+    /// Completes a result scored for the analysed start of the password from
+    /// the matches found in it, adding the feedback and timing
+    pub(crate) fn finish(&self,
+                         mut result: PasswordResult,
+                         matches: &[BaseMatch],
+                         password: &str,
+                         context: &MatchContext,
+                         start: Duration) -> PasswordResult {
+        if self.explain {
+            result.explanation = Some(scoring::explain(&result, context.scoring()));
+        }
+        result.alternatives = scoring::alternatives(&result.password,
+                                                    matches,
+                                                    context.scoring(),
+                                                    self.alternatives);
+        scoring::append_bruteforce(&mut result, password);
+        result.l33t_budget_exhausted = context.l33t_budget.exhausted();
+        result.truncated = context.timed_out();
+        if result.truncated {
+            result.guesses = cmp::min(result.guesses, TRUNCATED_GUESSES);
+            result.guesses_log10 = log10(result.guesses as f64);
+            if let Some(ref mut explanation) = result.explanation {
+                explanation.truncated_to = Some(TRUNCATED_GUESSES);
+            }
+            for alternative in result.alternatives.iter_mut() {
+                alternative.guesses = cmp::min(alternative.guesses, TRUNCATED_GUESSES);
+            }
+        }
+        result.get_feedback();
+        result.calculation_time = millis(self.clock.now().saturating_sub(start));
+        result
+    }
+}
+
+fn millis(duration: Duration) -> u32 {
+    let ms = duration.as_secs() * 1000 + u64::from(duration.subsec_millis());
+    cmp::min(ms, u64::from(u32::MAX)) as u32
+}
@@ -0,0 +1,279 @@
+use core::cmp;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+use rand::{CryptoRng, Rng};
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+use estimator::Estimator;
+use matching::DICTIONARIES;
+use prelude::*;
+use result::{PasswordResult, PasswordScore};
+
+/// Words a passphrase is drawn from
+#[derive(Clone, Debug, PartialEq)]
+pub enum WordList {
+    /// The `count` most common words of three or more letters in a built in
+    /// dictionary, named as in dictionary matches such as "Wikipedia"
+    BuiltIn { dictionary: String, count: usize },
+    /// Words the built in dictionaries may not contain. They're given to the
+    /// estimator as user inputs so passphrases are scored as if the attacker
+    /// has the list, which is slow for long lists.
+    Custom(Vec<String>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Capitalisation {
+    Lower,
+    /// Capitalise the first letter of every word
+    Title,
+    /// Capitalise the first letter of each word with a one in two chance
+    Random,
+}
+
+/// Strength a generated passphrase must be estimated at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Score(PasswordScore),
+    GuessesLog10(f64),
+}
+
+impl Target {
+    fn met_by(&self, result: &PasswordResult) -> bool {
+        match *self {
+            Target::Score(score) => match result.score {
+                Some(s) => s as u8 >= score as u8,
+                None => false,
+            },
+            Target::GuessesLog10(log10) => result.guesses_log10 >= log10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeneratorError {
+    /// The word list was empty or named no built in dictionary
+    NoWords,
+    /// No passphrase of up to `max_words` words met the target
+    TargetNotMet,
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GeneratorError::NoWords => write!(f, "no words to build a passphrase from"),
+            GeneratorError::TargetNotMet => {
+                write!(f, "no passphrase of the allowed lengths met the target")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for GeneratorError {}
+
+/// Generates random passphrases, checking each with the estimator until one
+/// meets the target
+#[derive(Clone, Debug)]
+pub struct Generator {
+    /// Defaults to the largest built in dictionary compiled in
+    pub words: WordList,
+    /// Number of words in the first passphrases tried. A word is added each
+    /// time `attempts` passphrases fall short of the target
+    pub min_words: usize,
+    pub max_words: usize,
+    /// Characters that may separate the words, one is picked for each
+    /// passphrase. The words are joined directly if there are none
+    pub separators: Vec<char>,
+    pub capitalisation: Capitalisation,
+    /// Number of random digits appended to one of the words
+    pub digits: usize,
+    pub target: Target,
+    /// Passphrases tried with each number of words
+    pub attempts: usize,
+}
+
+impl Default for Generator {
+    fn default() -> Generator {
+        Generator {
+            words: WordList::BuiltIn {
+                dictionary: String::from(largest_dictionary()),
+                count: 8192,
+            },
+            min_words: 4,
+            max_words: 10,
+            separators: vec!['-'],
+            capitalisation: Capitalisation::Lower,
+            digits: 0,
+            target: Target::Score(PasswordScore::Strong),
+            attempts: 8,
+        }
+    }
+}
+
+/// Name of the built in dictionary with the most words, preferring Wikipedia
+/// among equally large ones. Empty if no dictionary is compiled in.
+fn largest_dictionary() -> &'static str {
+    DICTIONARIES.iter()
+                .max_by_key(|&&(name, words)| (words.len(), name == "Wikipedia"))
+                .map_or("", |&(name, _)| name)
+}
+
+impl Generator {
+    pub fn new() -> Generator {
+        Default::default()
+    }
+
+    /// Generates a passphrase using the operating system's random number
+    /// generator. Returns the estimate for it, its `password` being the
+    /// passphrase
+    #[cfg(feature = "std")]
+    pub fn generate(&self, estimator: &Estimator) -> Result<PasswordResult, GeneratorError> {
+        self.generate_with_rng(estimator, &mut OsRng)
+    }
+
+    /// Generates a passphrase using the given cryptographically secure random
+    /// number generator
+    pub fn generate_with_rng<R>(&self,
+                                estimator: &Estimator,
+                                rng: &mut R) -> Result<PasswordResult, GeneratorError>
+        where R: Rng + CryptoRng + ?Sized
+    {
+        let words = self.word_list();
+        if words.is_empty() {
+            return Err(GeneratorError::NoWords);
+        }
+        let user_inputs = match self.words {
+            WordList::Custom(_) => words.iter().map(|w| w.as_str()).collect(),
+            WordList::BuiltIn { .. } => Vec::new(),
+        };
+        for count in cmp::max(self.min_words, 1)..self.max_words + 1 {
+            for _ in 0..self.attempts {
+                let passphrase = self.compose(&words, count, rng);
+                let result = estimator.estimate_with_inputs(&passphrase, &user_inputs);
+                // A truncated estimate may have missed how weak it is
+                if !result.truncated && self.target.met_by(&result) {
+                    return Ok(result);
+                }
+            }
+        }
+        Err(GeneratorError::TargetNotMet)
+    }
+
+    pub(crate) fn word_list(&self) -> Vec<String> {
+        match self.words {
+            WordList::BuiltIn { ref dictionary, count } => {
+                DICTIONARIES.iter()
+                            .find(|&&(name, _)| name == dictionary)
+                            .map_or(Vec::new(), |&(_, words)| {
+                                words.by_rank()
+                                     .into_iter()
+                                     .filter(|w| w.chars().count() >= 3)
+                                     .filter(|w| w.chars().all(char::is_alphabetic))
+                                     .take(count)
+                                     .collect()
+                            })
+            }
+            WordList::Custom(ref words) => {
+                words.iter().filter(|w| !w.is_empty()).cloned().collect()
+            }
+        }
+    }
+
+    fn compose<R>(&self, words: &[String], count: usize, rng: &mut R) -> String
+        where R: Rng + ?Sized
+    {
+        let separator = if self.separators.is_empty() {
+            None
+        } else {
+            Some(self.separators[rng.gen_range(0..self.separators.len())])
+        };
+        let digits_after = rng.gen_range(0..count);
+        let mut passphrase = String::new();
+        for i in 0..count {
+            if let (true, Some(separator)) = (i > 0, separator) {
+                passphrase.push(separator);
+            }
+            let word = words[rng.gen_range(0..words.len())].to_lowercase();
+            let capitalise = match self.capitalisation {
+                Capitalisation::Lower => false,
+                Capitalisation::Title => true,
+                Capitalisation::Random => rng.gen(),
+            };
+            let mut chars = word.chars();
+            if let (true, Some(first)) = (capitalise, chars.next()) {
+                passphrase.extend(first.to_uppercase());
+                passphrase.push_str(chars.as_str());
+            } else {
+                passphrase.push_str(&word);
+            }
+            if i == digits_after {
+                for _ in 0..self.digits {
+                    passphrase.push(char::from(b'0' + rng.gen_range(0..10u8)));
+                }
+            }
+        }
+        passphrase
+    }
+}
+
+
+#[test]
+fn generate_test() {
+    let estimator = Estimator::new();
+    let generator = Generator {
+        separators: vec!['.', '_'],
+        capitalisation: Capitalisation::Title,
+        digits: 2,
+        ..Generator::new()
+    };
+    let result = generator.generate(&estimator).unwrap();
+    assert!(result.score.unwrap() as u8 >= PasswordScore::Strong as u8);
+    assert_eq!(result.guesses, estimator.estimate(&result.password).guesses);
+
+    let separator = if result.password.contains('.') { '.' } else { '_' };
+    let words = result.password.split(separator).collect::<Vec<&str>>();
+    assert!(words.len() >= 4);
+    assert!(words.iter().all(|w| w.chars().next().unwrap().is_uppercase()));
+    let digits = result.password.chars().filter(|c| c.is_ascii_digit()).count();
+    assert_eq!(digits, 2);
+}
+
+#[test]
+fn default_dictionary_test() {
+    let longest = DICTIONARIES.iter().map(|&(_, words)| words.len()).max();
+    match Generator::new().words {
+        WordList::BuiltIn { ref dictionary, .. } => {
+            let words = DICTIONARIES.iter()
+                                    .find(|&&(name, _)| name == dictionary)
+                                    .map(|&(_, words)| words.len());
+            assert_eq!(words, longest);
+        }
+        WordList::Custom(_) => panic!("default word list isn't built in"),
+    }
+}
+
+#[test]
+fn generate_custom_words_test() {
+    let estimator = Estimator::new();
+    let mut generator = Generator {
+        words: WordList::Custom(vec![String::from("zorblax"), String::from("quintessimal")]),
+        separators: Vec::new(),
+        target: Target::GuessesLog10(12.0),
+        max_words: 5,
+        attempts: 2,
+        ..Generator::new()
+    };
+    // Knowing the list, a few words from two give an attacker few guesses
+    assert_eq!(generator.generate(&estimator).err(), Some(GeneratorError::TargetNotMet));
+
+    generator.target = Target::GuessesLog10(3.0);
+    let result = generator.generate(&estimator).unwrap();
+    assert!(result.guesses_log10 >= 3.0);
+
+    generator.words = WordList::BuiltIn {
+        dictionary: String::from("Klingon"),
+        count: 100,
+    };
+    assert_eq!(generator.generate(&estimator).err(), Some(GeneratorError::NoWords));
+}
@@ -0,0 +1,278 @@
+#[cfg(feature = "std")]
+use std::error::Error;
+use prelude::*;
+
+mod layout;
+
+pub use self::layout::{Key, KeyboardStyle, LayoutError};
+
+// KEYBOARD_LAYOUTS, the built in layouts and those read from files, already
+// parsed by the build script
+include!(concat!(env!("OUT_DIR"), "/keyboard_layouts.rs"));
+
+lazy_static! {
+    /// Keyboards searched for spatial patterns by default. These are the
+    /// layouts from the original zxcvbn followed by any layouts described in
+    /// text files at build time.
+    pub static ref DEFAULT_KEYBOARDS: Vec<(String, Keyboard)> = {
+        KEYBOARD_LAYOUTS.iter()
+                        .map(|&(name, style, keys)| (name.to_string(), graph_from_keys(keys, style)))
+                        .collect()
+    };
+}
+
+impl Key {
+    pub fn is_shifted(&self, c: char) -> bool {
+        self.shifted == c && c != '\0'
+    }
+}
+
+/// Direction of a neighbouring key along one axis
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// Above or to the left
+    Previous,
+    Same,
+    /// Below or to the right
+    Next,
+}
+
+/// Position of a key relative to the key it neighbours
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Edge {
+    pub horizontal: Direction,
+    pub vertical: Direction,
+}
+
+/// Graph of the keys on a keyboard with an edge from every key to each of its
+/// neighbours
+#[derive(Clone, Debug, Default)]
+pub struct Keyboard {
+    keys: Vec<Key>,
+    /// Neighbours of each key, indexed the same as `keys`
+    neighbours: Vec<Vec<(Key, Edge)>>,
+}
+
+impl Keyboard {
+    pub fn new() -> Keyboard {
+        Default::default()
+    }
+
+    fn index(&self, key: Key) -> Option<usize> {
+        self.keys.iter().position(|k| *k == key)
+    }
+
+    /// Adds a key without any neighbours if it isn't already in the graph
+    pub fn add_node(&mut self, key: Key) {
+        self.insert(key);
+    }
+
+    fn insert(&mut self, key: Key) -> usize {
+        match self.index(key) {
+            Some(i) => i,
+            None => {
+                self.keys.push(key);
+                self.neighbours.push(Vec::new());
+                self.keys.len() - 1
+            }
+        }
+    }
+
+    /// Adds an edge from `a` to its neighbour `b`, replacing any existing edge
+    /// between them. Keys not in the graph are added.
+    pub fn add_edge(&mut self, a: Key, b: Key, edge: Edge) {
+        self.insert(b);
+        let i = self.insert(a);
+        let neighbours = &mut self.neighbours[i];
+        match neighbours.iter().position(|&(k, _)| k == b) {
+            Some(j) => neighbours[j].1 = edge,
+            None => neighbours.push((b, edge)),
+        }
+    }
+
+    /// Finds the key typing the character, shifted or not
+    pub fn find_key(&self, c: char) -> Option<Key> {
+        if c == '\0' {
+            None
+        } else {
+            self.keys.iter().find(|k| k.value == c || k.shifted == c).cloned()
+        }
+    }
+
+    /// Position of `b` relative to `a`, if they are neighbours
+    pub fn edge_weight(&self, a: Key, b: Key) -> Option<&Edge> {
+        self.index(a)
+            .and_then(|i| self.neighbours[i].iter().find(|&&(k, _)| k == b))
+            .map(|&(_, ref edge)| edge)
+    }
+
+    pub fn neighbors(&self, key: Key) -> impl Iterator<Item = Key> + '_ {
+        self.index(key)
+            .map_or(&[][..], |i| &self.neighbours[i][..])
+            .iter()
+            .map(|&(k, _)| k)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = Key> + '_ {
+        self.keys.iter().cloned()
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.neighbours.iter().map(|n| n.len()).sum()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for LayoutError {}
+
+/// Relative positions of the neighbours of a key, in the same order as the
+/// adjacency graph scripts from dropbox's zxcvbn
+fn neighbour_offsets(style: KeyboardStyle) -> &'static [(i32, i32)] {
+    match style {
+        KeyboardStyle::Slanted => &[(-1, 0), (0, -1), (1, -1), (1, 0), (0, 1), (-1, 1)],
+        KeyboardStyle::Aligned => &[(-1, 0), (-1, -1), (0, -1), (1, -1),
+                                     (1, 0), (1, 1), (0, 1), (-1, 1)],
+    }
+}
+
+fn to_direction(offset: i32) -> Direction {
+    match offset {
+        -1 => Direction::Previous,
+        1 => Direction::Next,
+        _ => Direction::Same,
+    }
+}
+
+/// Generates a keyboard graph from a text grid in the style of the original
+/// zxcvbn adjacency graph source. Each key is written as its unshifted
+/// character optionally followed by its shifted character, keys are separated
+/// by a single space and every key must be the same width. For slanted
+/// keyboards each row is indented one character further than the row above.
+///
+/// ```text
+/// `~ 1! 2@ 3# 4$ 5% 6^ 7& 8* 9( 0) -_ =+
+///     qQ wW eE rR tT yY uU iI oO pP [{ ]} \|
+///      aA sS dD fF gG hH jJ kK lL ;: '"
+///       zZ xX cC vV bB nN mM ,< .> /?
+/// ```
+pub fn parse_layout(layout: &str, style: KeyboardStyle) -> Result<Keyboard, LayoutError> {
+    layout::grid_keys(layout, style).map(|keys| graph_from_keys(&keys, style))
+}
+
+/// Builds the graph of keys found on a grid, with an edge from each key to
+/// every key next to it
+fn graph_from_keys(keys: &[((i32, i32), Key)], style: KeyboardStyle) -> Keyboard {
+    let positions = keys.iter().cloned().collect::<HashMap<(i32, i32), Key>>();
+    let mut result = Keyboard::new();
+    for &(_, key) in keys.iter() {
+        result.add_node(key);
+    }
+    for &((x, y), key) in keys.iter() {
+        for &(dx, dy) in neighbour_offsets(style).iter() {
+            if let Some(neighbour) = positions.get(&(x + dx, y + dy)) {
+                let edge = Edge {
+                    horizontal: to_direction(dx),
+                    vertical: to_direction(dy),
+                };
+                result.add_edge(key, *neighbour, edge);
+            }
+        }
+    }
+    result
+}
+
+/// Parses a layout description file. The first non-blank line gives the
+/// keyboard style ("slanted" or "aligned") and the remaining lines are the
+/// grid as accepted by `parse_layout`. Lines starting with '#' before the
+/// style are treated as comments.
+pub fn parse_layout_description(description: &str) -> Result<Keyboard, LayoutError> {
+    layout::description_keys(description).map(|(style, keys)| graph_from_keys(&keys, style))
+}
+
+/// Rounded average number of neighbours per key, used when scoring spatial
+/// matches
+pub fn average_degree(keyboard: &Keyboard) -> u64 {
+    round(keyboard.edge_count() as f64 / keyboard.node_count() as f64) as u64
+}
+
+
+#[test]
+fn default_keyboards_test() {
+    // Keys, edges and the average degree used for scoring, which match the
+    // adjacency graphs in the original zxcvbn
+    let expected = [("qwerty", 47, 216, 5), ("dvorak", 47, 216, 5),
+                    ("Keypad", 15, 76, 5), ("Mac keypad", 16, 84, 5)];
+    for (&(name, nodes, edges, degree), (n, graph)) in expected.iter().zip(DEFAULT_KEYBOARDS.iter()) {
+        assert_eq!(name, n);
+        assert_eq!(graph.node_count(), nodes, "{}", name);
+        assert_eq!(graph.edge_count(), edges, "{}", name);
+        assert_eq!(average_degree(graph), degree, "{}", name);
+    }
+
+    let qwerty = &DEFAULT_KEYBOARDS[0].1;
+    let q = qwerty.find_key('q').unwrap();
+    let w = qwerty.find_key('W').unwrap();
+    assert!(w.is_shifted('W') && !w.is_shifted('w'));
+    assert_eq!(qwerty.edge_weight(q, w),
+               Some(&Edge { horizontal: Direction::Next, vertical: Direction::Same }));
+    assert_eq!(qwerty.edge_weight(q, qwerty.find_key('a').unwrap()),
+               Some(&Edge { horizontal: Direction::Same, vertical: Direction::Next }));
+    assert!(qwerty.edge_weight(q, qwerty.find_key('s').unwrap()).is_none());
+}
+
+#[test]
+fn parse_layout_description_test() {
+    let numpad = "# Kiosk numpad\naligned\n7 8 9\n4 5 6\n1 2 3\n  0";
+    let graph = parse_layout_description(numpad).unwrap();
+    assert_eq!(graph.node_count(), 10);
+    let five = graph.find_key('5').unwrap();
+    assert_eq!(graph.neighbors(five).count(), 8);
+    let zero = graph.find_key('0').unwrap();
+    assert_eq!(graph.neighbors(zero).count(), 3);
+
+    assert_eq!(parse_layout_description("7 8 9").unwrap_err(),
+               LayoutError::MissingStyle);
+    assert_eq!(parse_layout("ab c", KeyboardStyle::Aligned).unwrap_err(),
+               LayoutError::BadToken { line: 1, token: String::from("c") });
+    assert_eq!(parse_layout("a  b", KeyboardStyle::Aligned).unwrap_err(),
+               LayoutError::Misaligned { line: 1, column: 4 });
+    assert_eq!(parse_layout("a b\nb c", KeyboardStyle::Aligned).unwrap_err(),
+               LayoutError::DuplicateKey { line: 2, key: 'b' });
+    assert_eq!(parse_layout("\n  \n", KeyboardStyle::Slanted).unwrap_err(),
+               LayoutError::Empty);
+
+    // Lines are counted from the start of the text given, so they can be
+    // reported against the file a description was read from
+    let error = parse_layout_description("# Kiosk\n\naligned\n\n1 2\n3  4").unwrap_err();
+    assert_eq!(error, LayoutError::Misaligned { line: 6, column: 4 });
+    assert_eq!(error.line(), Some(6));
+    assert_eq!(error.to_string(), "key at column 4 is not aligned to the grid");
+    assert_eq!(parse_layout("\nab c", KeyboardStyle::Aligned).unwrap_err().line(), Some(2));
+}
+
+#[test]
+fn custom_keyboard_test() {
+    use estimator::Estimator;
+
+    let mut estimator = Estimator::new();
+    let layout = "aligned\n\
+                  z y x w\n\
+                  v u t s\n\
+                  r q p o";
+    let before = estimator.omnimatch("zvrqpo")
+                          .into_iter()
+                          .filter(|m| m.pattern == "Spatial")
+                          .count();
+    assert_eq!(before, 0);
+
+    let graph = parse_layout_description(layout).unwrap();
+    estimator.keyboards.push((String::from("kiosk"), graph));
+    let matches = estimator.omnimatch("zvrqpo");
+    let spatial = matches.iter().find(|m| m.pattern == "Spatial").unwrap();
+    assert_eq!(spatial.token, "zvrqpo");
+    assert!(estimator.add_keyboard("broken", "ab c", KeyboardStyle::Aligned).is_err());
+}
@@ -0,0 +1,159 @@
+// Parsing of keyboard layout text. The build script includes this file to
+// check the layouts it compiles in, so it only uses core and alloc.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A key on a keyboard. Keys without a shifted character use '\0'.
+#[derive(Hash, Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Key {
+    /// Character typed by the key
+    pub value: char,
+    /// Character typed with shift held
+    pub shifted: char,
+}
+
+/// Physical arrangement of the keys in a layout. Slanted keyboards offset each
+/// row by one character from the row above, giving each key up to 6
+/// neighbours. Aligned keyboards such as numpads are a straight grid with up
+/// to 8 neighbours per key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyboardStyle {
+    Slanted,
+    Aligned,
+}
+
+/// Errors found while turning a text description into a keyboard graph. Line
+/// and column numbers start from 1. The line is given by `line` rather than in
+/// the message so callers can report it alongside the file it came from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LayoutError {
+    /// The layout contained no keys
+    Empty,
+    /// The description didn't start with "slanted" or "aligned"
+    MissingStyle,
+    /// A key token wasn't one or two characters or differed in size from the
+    /// first key
+    BadToken { line: usize, token: String },
+    /// A key didn't start on a column matching the grid
+    Misaligned { line: usize, column: usize },
+    /// A character appeared on more than one key
+    DuplicateKey { line: usize, key: char },
+}
+
+impl LayoutError {
+    /// Line of the layout the error was found on, if it belongs to one
+    pub fn line(&self) -> Option<usize> {
+        match *self {
+            LayoutError::Empty | LayoutError::MissingStyle => None,
+            LayoutError::BadToken { line, .. } |
+            LayoutError::Misaligned { line, .. } |
+            LayoutError::DuplicateKey { line, .. } => Some(line),
+        }
+    }
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LayoutError::Empty => write!(f, "layout contains no keys"),
+            LayoutError::MissingStyle => {
+                write!(f, "layout must start with \"slanted\" or \"aligned\"")
+            }
+            LayoutError::BadToken { ref token, .. } => {
+                write!(f, "key \"{}\" has the wrong number of characters", token)
+            }
+            LayoutError::Misaligned { column, .. } => {
+                write!(f, "key at column {} is not aligned to the grid", column)
+            }
+            LayoutError::DuplicateKey { key, .. } => {
+                write!(f, "'{}' appears on more than one key", key)
+            }
+        }
+    }
+}
+
+/// Keys found on a layout grid with their column and row
+pub type GridKeys = Vec<((i32, i32), Key)>;
+
+/// Finds the keys in a text grid, see `keyboard::parse_layout`, with their
+/// column and row on the grid
+pub fn grid_keys(layout: &str, style: KeyboardStyle) -> Result<GridKeys, LayoutError> {
+    grid_keys_from(layout, style, 0)
+}
+
+/// Finds the style and keys of a layout description, see
+/// `keyboard::parse_layout_description`. Lines are counted from the start of
+/// the description.
+pub fn description_keys(description: &str) -> Result<(KeyboardStyle, GridKeys), LayoutError> {
+    let mut lines = description.lines().enumerate();
+    let (style, style_line) = loop {
+        match lines.next().map(|(i, l)| (i, l.trim())) {
+            Some((_, l)) if l.is_empty() || l.starts_with('#') => continue,
+            Some((i, "slanted")) => break (KeyboardStyle::Slanted, i + 1),
+            Some((i, "aligned")) => break (KeyboardStyle::Aligned, i + 1),
+            _ => return Err(LayoutError::MissingStyle),
+        }
+    };
+    let grid = lines.map(|(_, l)| l).collect::<Vec<&str>>().join("\n");
+    grid_keys_from(&grid, style, style_line).map(|keys| (style, keys))
+}
+
+/// Finds the keys in a grid starting after `first_line` lines of a file
+fn grid_keys_from(layout: &str,
+                  style: KeyboardStyle,
+                  first_line: usize) -> Result<GridKeys, LayoutError> {
+    let blank = layout.lines().take_while(|l| l.trim().is_empty()).count();
+    let rows = layout.lines().skip(blank).collect::<Vec<&str>>();
+
+    let token_size = match rows.first().and_then(|r| r.split_whitespace().next()) {
+        Some(t) => t.chars().count(),
+        None => return Err(LayoutError::Empty),
+    };
+    let x_unit = token_size + 1;
+
+    let mut keys: GridKeys = Vec::new();
+    for (y, row) in rows.iter().enumerate() {
+        let line = first_line + blank + y + 1;
+        let slant = if style == KeyboardStyle::Slanted { y } else { 0 };
+        let chars = row.chars().collect::<Vec<char>>();
+        let mut column = 0;
+        while column < chars.len() {
+            if chars[column].is_whitespace() {
+                column += 1;
+                continue;
+            }
+            let token = chars[column..].iter()
+                                       .take_while(|c| !c.is_whitespace())
+                                       .cloned()
+                                       .collect::<String>();
+            let size = token.chars().count();
+            if size != token_size || size > 2 {
+                return Err(LayoutError::BadToken { line, token });
+            }
+            if column < slant || (column - slant) % x_unit != 0 {
+                return Err(LayoutError::Misaligned {
+                    line,
+                    column: column + 1,
+                });
+            }
+            let mut token_chars = token.chars();
+            let key = Key {
+                value: token_chars.next().unwrap(),
+                shifted: token_chars.next().unwrap_or('\0'),
+            };
+            for &c in [key.value, key.shifted].iter().filter(|c| **c != '\0') {
+                if keys.iter().any(|&(_, k)| k.value == c || k.shifted == c) {
+                    return Err(LayoutError::DuplicateKey { line, key: c });
+                }
+            }
+            keys.push(((((column - slant) / x_unit) as i32, y as i32), key));
+            column += size;
+        }
+    }
+    if keys.is_empty() {
+        return Err(LayoutError::Empty);
+    }
+    Ok(keys)
+}
@@ -1,10 +1,54 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+// core is only in scope by itself without std. pyo3's macros also refer to
+// ::core, which in this edition means the crate root
+#[cfg(feature = "std")]
+extern crate core;
 #[macro_use]
 extern crate lazy_static;
+#[cfg(feature = "std")]
 extern crate regex;
 extern crate chrono;
-extern crate fancy_regex;
-extern crate keygraph_rs;
+extern crate rand;
+#[cfg(not(feature = "std"))]
+extern crate hashbrown;
+#[cfg(not(feature = "std"))]
+extern crate libm;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "wasm")]
+extern crate js_sys;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "breach")]
+extern crate sha1;
+#[cfg(feature = "breach")]
+extern crate md4;
+#[cfg(feature = "blocklist")]
+extern crate memmap2;
 
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "blocklist")]
+pub mod blocklist;
+#[cfg(feature = "breach")]
+pub mod breach;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod clock;
+pub mod dictionary;
+pub mod edit;
+pub mod estimator;
+pub mod generator;
+pub mod keyboard;
 pub mod matching;
+mod prelude;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod result;
 pub mod scoring;
+pub mod session;
+#[cfg(feature = "wasm")]
+pub mod wasm;
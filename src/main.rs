@@ -1,14 +1,12 @@
 extern crate zxcvbn_rs;
 
-use zxcvbn_rs::{matching, scoring, result};
+use zxcvbn_rs::estimator::Estimator;
 use std::env;
 
 fn zxcvbn(password: String, user_dictionary: Vec<String>) {
-    let matches = matching::omnimatch(password.as_ref());
-    let mut best_sequence = scoring::most_guessable_match_sequence(password, 
-                                                                   matches, 
-                                                                   false);
-    best_sequence.get_feedback();
+    let estimator = Estimator::new();
+    let user_inputs = user_dictionary.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+    let best_sequence = estimator.estimate_with_inputs(password.as_ref(), &user_inputs);
     println!("{}", best_sequence);
 }
 
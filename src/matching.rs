@@ -1,13 +1,23 @@
-use std::collections::HashMap;
-use std::cmp::Ordering;
-use std::cmp;
-use std::iter::Iterator;
-use fancy_regex::Regex as FancyRegex;
+use core::cell::{Cell, RefCell};
+use core::cmp::Ordering;
+use core::cmp;
+use core::iter::{self, Iterator};
+use core::time::Duration;
+#[cfg(feature = "blocklist")]
+use blocklist::blocklist_match;
+#[cfg(feature = "breach")]
+use breach::breach_match;
+use clock::{Clock, DEFAULT_CLOCK};
+use dictionary::Dictionary;
+#[cfg(feature = "std")]
 use regex::Regex;
-use chrono::{NaiveDate, Datelike, Local};
-use scoring;
-use keygraph_rs::*;
+use chrono::{NaiveDate, Datelike};
+use scoring::{self, ScoringConfig};
+use estimator::{Estimator, DEFAULT_ESTIMATOR};
+use keyboard::{self, Edge, Key, Keyboard, DEFAULT_KEYBOARDS};
+use prelude::*;
 
+// Defines DICTIONARIES, the dictionaries build.rs compiled in
 include!(concat!(env!("OUT_DIR"), "/frequency_data.rs"));
 
 lazy_static! {
@@ -15,38 +25,43 @@ lazy_static! {
     /// However, this complicates the logic and requires another map to be made
     /// inside the l33t_dictionary_match. This was deemed a cleaner and simpler
     /// implementation.
-    static ref L33T_TABLE: HashMap<char, &'static str> = {
+    ///
+    /// Keys are the l33t tokens and can be more than one character long, values
+    /// are the letters the token may stand for.
+    pub static ref DEFAULT_L33T_TABLE: HashMap<String, String> = {
         let mut m = HashMap::new();
-        m.insert('4', "a");
-        m.insert('@', "a");
-        m.insert('8', "b");
-        m.insert('(', "c");
-        m.insert('{', "c");
-        m.insert('[', "c");
-        m.insert('<', "c");
-        m.insert('3', "e");
-        m.insert('6', "g");
-        m.insert('9', "g");
-        m.insert('1', "il");
-        m.insert('!', "il");
-        m.insert('|', "i");
-        m.insert('7', "lt");
-        m.insert('0', "o");
-        m.insert('$', "s");
-        m.insert('5', "s");
-        m.insert('+', "t");
-        m.insert('%', "x");
-        m.insert('2', "z");
+        for &(k, v) in [("4", "a"), ("@", "a"), ("/\\", "a"), ("8", "b"),
+                        ("(", "c"), ("{", "c"), ("[", "c"), ("<", "c"),
+                        ("3", "e"), ("ph", "f"), ("6", "g"), ("9", "g"),
+                        ("|-|", "h"), ("1", "il"), ("!", "il"), ("|", "i"),
+                        ("7", "lt"), ("0", "o"), ("$", "s"), ("5", "s"),
+                        ("+", "t"), ("vv", "w"), ("%", "x"), ("2", "z")].iter() {
+            m.insert(k.to_string(), v.to_string());
+        }
         m
     };
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct L33tData {
-    /// Hashmap containing a key of l33t characters and a string of the characters
+    /// Hashmap containing a key of l33t tokens and a string of the characters
     /// they replace
-    pub l33t_subs: HashMap<char, String>,
+    pub l33t_subs: HashMap<String, String>,
+    /// Every substitution made in the match, in the order they appear
+    pub spans: Vec<L33tSpan>,
+}
 
+/// A single l33t substitution within a match
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct L33tSpan {
+    /// Index of the first character of the l33t token in the password
+    pub start: usize,
+    /// Index of the last character of the l33t token in the password
+    pub end: usize,
+    /// The l33t token as it appears in the password
+    pub token: String,
+    /// The letter the token was taken to stand for
+    pub letter: char,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -64,6 +79,12 @@ pub enum MatchData {
         graph: String,
         turns: usize,
         shifted_count: usize,
+        /// Repeated or skipped keys in the walk, see `tolerant_spatial_match`
+        slips: usize,
+        /// Number of keys on the keyboard the match was found on
+        starting_positions: usize,
+        /// Average number of neighbours of a key on the keyboard
+        average_degree: u64,
     },
     Repeat {
         base_token: String,
@@ -82,6 +103,12 @@ pub enum MatchData {
         separator: char,
         date: NaiveDate, 
     },
+    /// The whole password was found in breached password files, see
+    /// `breach::BreachList`
+    Breach {
+        /// Times the password was seen in breaches
+        count: u64,
+    },
 }
 
 #[derive(Clone, Debug, Eq)]
@@ -113,65 +140,528 @@ impl PartialEq for BaseMatch {
     }
 }
 
-pub fn matches_from_all_dicts(password: &str, 
-                              matcher: &Fn(&str, &str, &[&str])->Vec<BaseMatch>) -> Vec<BaseMatch> {
-    
-    let dicts:HashMap<&str, &[&str]> = {
-        let mut m = HashMap::new();
-        m.insert("Female names", FEMALE_NAMES);
-        m.insert("Male names", MALE_NAMES);
-        m.insert("Surnames", SURNAMES);
-        m.insert("Passwords", PASSWORDS);
-        m.insert("Wikipedia", ENGLISH_WIKIPEDIA);
-        m.insert("TV and Film", US_TV_AND_FILM);
-        m
+/// Slices a string by char indices rather than byte offsets. Match start and
+/// end positions are char indices so non-ASCII passwords can be sliced safely.
+pub fn char_slice(s: &str, start: usize, end: usize) -> &str {
+    let mut offsets = s.char_indices().map(|(i, _)| i).chain(Some(s.len()));
+    let from = offsets.nth(start).unwrap_or(s.len());
+    let to = if end > start {
+        offsets.nth(end - start - 1).unwrap_or(s.len())
+    } else {
+        from
     };
+    &s[from..to]
+}
+
+/// Converts a byte offset into a string to a char index
+fn char_index(s: &str, byte_offset: usize) -> usize {
+    s[..byte_offset].chars().count()
+}
 
-    dicts.iter()
-         .map(|(&k, &v)| matcher(password, k, v))
-         .flat_map(|x| x.into_iter())
-         .collect::<Vec<BaseMatch>>()
+/// Lowercases a char to a single char so lowercased text lines up with the
+/// password char for char. Chars that lowercase to several, such as 'İ', keep
+/// only the first.
+fn lower_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+pub fn matches_from_all_dicts(password: &str, 
+                              matcher: &dyn Fn(&str, &str, &Dictionary)->Vec<BaseMatch>) -> Vec<BaseMatch> {
+    DICTIONARIES.iter()
+                .map(|&(k, v)| matcher(password, k, v))
+                .flat_map(|x| x.into_iter())
+                .collect::<Vec<BaseMatch>>()
 }
 
 /// Matches the password against every matcher returning the matches
 pub fn omnimatch(password: &str) -> Vec<BaseMatch> {
-    
-    let default_regex:HashMap<String, Regex> = {
-        let mut m = HashMap::new();
-        m.insert(String::from("recent year"), 
-                 Regex::new(r"19\d\d|200\d|201\d").unwrap());
-        m
+    DEFAULT_ESTIMATOR.omnimatch(password)
+}
+
+/// State shared by the matchers while matching a password, including the
+/// recursive matching of the base tokens of repeats
+pub(crate) struct MatchContext {
+    pub l33t_budget: L33tBudget,
+    /// Guesses for the base tokens of repeats already analysed
+    repeat_guesses: RefCell<HashMap<String, u64>>,
+    /// Lowercased words related to the user, such as their name or email,
+    /// matched as an extra dictionary
+    user_inputs: Dictionary,
+    clock: &'static dyn Clock,
+    /// Clock reading after which no more matchers, l33t variants or repeats
+    /// are tried
+    deadline: Option<Duration>,
+    timed_out: Cell<bool>,
+    /// Year dates and recent years are compared against, read once so every
+    /// match in an estimate agrees on it
+    pub reference_year: i32,
+    /// Matches and scores the way zxcvbn 4.4 does, see `Estimator::compat`
+    pub compat: bool,
+    /// See `Estimator::cumulative_frequency`
+    pub cumulative_frequency: bool,
+}
+
+impl MatchContext {
+    pub fn new(estimator: &Estimator) -> MatchContext {
+        MatchContext::with_user_inputs(estimator, &[])
+    }
+
+    pub fn with_user_inputs(estimator: &Estimator, user_inputs: &[&str]) -> MatchContext {
+        // A budget too large to add to the clock never runs out
+        let deadline = estimator.time_budget.and_then(|budget| estimator.clock.now().checked_add(budget));
+        MatchContext {
+            l33t_budget: L33tBudget::with_deadline(estimator.l33t_budget, estimator.clock, deadline),
+            repeat_guesses: RefCell::new(HashMap::new()),
+            user_inputs: {
+                let lower = user_inputs.iter()
+                                       .map(|s| s.to_lowercase())
+                                       .filter(|s| !s.is_empty())
+                                       .collect::<Vec<String>>();
+                Dictionary::new(&lower.iter().map(|s| s.as_str()).collect::<Vec<&str>>())
+            },
+            clock: estimator.clock,
+            deadline,
+            timed_out: Cell::new(false),
+            reference_year: estimator.clock.current_year(),
+            compat: estimator.compat,
+            cumulative_frequency: estimator.cumulative_frequency,
+        }
+    }
+
+    /// Scoring for the matches found. zxcvbn adds the additive term to the
+    /// guesses for a sequence, the estimator otherwise leaves it out.
+    pub fn scoring(&self) -> ScoringConfig {
+        ScoringConfig {
+            additive: self.compat,
+            reference_year: self.reference_year,
+            compat: self.compat,
+            cumulative_frequency: self.cumulative_frequency && !self.compat,
+        }
+    }
+
+    /// True once the deadline has passed, some matchers may have been skipped
+    pub fn timed_out(&self) -> bool {
+        if !self.timed_out.get() {
+            if let Some(deadline) = self.deadline {
+                self.timed_out.set(self.clock.now() >= deadline);
+            }
+        }
+        self.timed_out.get()
+    }
+
+    /// Length of the longest match the local matchers can find
+    pub fn longest_local_match(&self) -> usize {
+        cmp::max(*LONGEST_LOCAL_MATCH, self.user_inputs.longest())
+    }
+}
+
+type DictionaryMatcher<'a> = dyn Fn(&str, &str, &Dictionary) -> Vec<BaseMatch> + 'a;
+
+/// Runs a dictionary matcher over the built in dictionaries and the user inputs
+/// given for the password
+fn matches_from_dicts_and_inputs(password: &str,
+                                 matcher: &DictionaryMatcher<'_>,
+                                 context: &MatchContext) -> Vec<BaseMatch> {
+    let mut result = matches_from_all_dicts(password, matcher);
+    if !context.user_inputs.is_empty() {
+        result.append(&mut matcher(password, "User inputs", &context.user_inputs));
+    }
+    result
+}
+
+type Matcher<'a> = dyn Fn(&str) -> Vec<BaseMatch> + 'a;
+
+lazy_static! {
+    /// Longest match the local matchers can find, the longest dictionary word
+    /// or a date with separators
+    pub(crate) static ref LONGEST_LOCAL_MATCH: usize = {
+        DICTIONARIES.iter()
+            .map(|&(_, d)| d.longest())
+            .fold(10, cmp::max)
+    };
+
+    /// The counts in each dictionary that has them, largest first, with the
+    /// dictionary's total
+    static ref FREQUENCIES: Vec<(&'static str, Vec<u64>, u64)> = {
+        DICTIONARIES.iter()
+            .filter(|&&(_, d)| d.total() > 0)
+            .map(|&(name, d)| (name, d.frequencies(), d.total()))
+            .collect()
     };
+}
+
+/// Guesses to reach a word in a built in dictionary when every dictionary with
+/// counts is tried at once, most frequent word first. Frequencies are relative
+/// to each dictionary's total so large lists don't crowd out small ones. This
+/// is the word's rank plus the words in the other dictionaries more frequent
+/// than it. None if the word has no count.
+pub(crate) fn frequency_rank(dictionary_name: &str, word: &str) -> Option<usize> {
+    let dictionary = DICTIONARIES.iter().find(|&&(name, _)| name == dictionary_name)?.1;
+    let (rank, count, total) = (dictionary.rank(word)?, dictionary.count(word)?, dictionary.total());
+    let more_frequent = FREQUENCIES.iter()
+                                   .filter(|f| f.0 != dictionary_name)
+                                   .map(|&(_, ref counts, other_total)| {
+                                       // count / total < c / other_total
+                                       let threshold = count as u128 * other_total as u128;
+                                       counts.partition_point(|&c| c as u128 * total as u128 > threshold)
+                                   })
+                                   .sum::<usize>();
+    Some(rank + more_frequent)
+}
 
+/// Runs matchers in order until the context's deadline passes
+fn run_matchers(password: &str,
+                matchers: &[&Matcher<'_>],
+                context: &MatchContext) -> Vec<BaseMatch> {
     let mut result:Vec<BaseMatch> = Vec::new();
+    for matcher in matchers.iter() {
+        if context.timed_out() {
+            break;
+        }
+        result.append(&mut matcher(password));
+    }
+    result
+}
 
-    result.append(&mut matches_from_all_dicts(password, &dictionary_match));
-    result.append(&mut matches_from_all_dicts(password, &reverse_dictionary_match));
-    result.append(&mut matches_from_all_dicts(password, &l33t_match));
-    result.append(&mut sequence_match(password));
-    result.append(&mut regex_match(password, default_regex));
-    result.append(&mut date_match(password));
-    result.append(&mut repeat_match(password));
-    result.append(&mut spatial_match(password));
-    
+/// Matches the password against every matcher using the configuration in the
+/// estimator. If the context's deadline passes the remaining matchers are
+/// skipped.
+pub(crate) fn omnimatch_with(password: &str,
+                             estimator: &Estimator,
+                             context: &MatchContext) -> Vec<BaseMatch> {
+    let mut result = local_match_with(password, context);
+    result.append(&mut global_match_with(password, estimator, context));
     result.sort();
     result
 }
 
+/// Runs the matchers whose matches only depend on the characters they cover,
+/// so adding characters to a password never changes the existing matches.
+/// None of these matches are longer than `MatchContext::longest_local_match`.
+pub(crate) fn local_match_with(password: &str, context: &MatchContext) -> Vec<BaseMatch> {
+    let dictionary = |p: &str| matches_from_dicts_and_inputs(p, &dictionary_match, context);
+    let reverse = |p: &str| {
+        matches_from_dicts_and_inputs(p, &reverse_dictionary_match, context)
+    };
+    let date = |p: &str| date_match_with(p, context.reference_year);
+    if context.compat {
+        // zxcvbn's dates are filtered against longer ones found after them,
+        // so they're matched with the global matchers
+        run_matchers(password, &[&dictionary, &reverse], context)
+    } else {
+        run_matchers(password, &[&dictionary, &reverse, &date], context)
+    }
+}
+
+/// Runs the matchers whose matches can depend on the rest of the password,
+/// such as sequences and repeats which extend as far as they can
+pub(crate) fn global_match_with(password: &str,
+                                estimator: &Estimator,
+                                context: &MatchContext) -> Vec<BaseMatch> {
+    let mut matches = GlobalMatches::default();
+    matches.rematch(password, 0, estimator, context);
+    matches.matches()
+}
+
+/// Matches of the global matchers kept between the estimates of a session,
+/// so an edit only rematches what it can reach. The scanning matchers restart
+/// from the last point the unchanged start of the password decides, and l33t
+/// regions the edit doesn't reach are replayed. A repeat can start before any
+/// earlier one once characters are added, and breaches and blocklists look up
+/// the whole password, so those are rematched in full.
+#[derive(Default)]
+pub(crate) struct GlobalMatches {
+    /// Length in chars of the password matched
+    length: usize,
+    l33t: Vec<BaseMatch>,
+    l33t_regions: L33tRegions,
+    sequence: Vec<BaseMatch>,
+    years: Vec<BaseMatch>,
+    repeat: Vec<BaseMatch>,
+    /// Walks found on each pass of the spatial matcher
+    walks: Vec<Vec<BaseMatch>>,
+    spatial: Vec<BaseMatch>,
+    /// zxcvbn's dates, which are dropped inside longer ones found after them
+    dates: Vec<BaseMatch>,
+    breach: Vec<BaseMatch>,
+    blocklist: Vec<BaseMatch>,
+}
+
+impl GlobalMatches {
+    /// Matches a password whose first `unchanged` chars are the same as the
+    /// password last matched. If the context's deadline passes the remaining
+    /// matchers are skipped.
+    pub fn rematch(&mut self,
+                   password: &str,
+                   unchanged: usize,
+                   estimator: &Estimator,
+                   context: &MatchContext) {
+        let length = password.chars().count();
+        let unchanged = cmp::min(unchanged, cmp::min(length, self.length));
+        #[cfg(any(feature = "breach", feature = "blocklist"))]
+        let changed = unchanged < length || length != self.length;
+        self.length = length;
+
+        if context.timed_out() {
+            self.l33t.clear();
+        } else if context.compat {
+            self.l33t = matches_from_dicts_and_inputs(password, &zxcvbn_l33t_match, context);
+        } else {
+            let known = &mut self.l33t_regions;
+            self.l33t = matches_from_dicts_and_inputs(password, &|p, n, d| {
+                l33t_match_replaying(p, n, d, &estimator.l33t_table, &context.l33t_budget, known)
+            }, context);
+            known.advance();
+        }
+
+        if context.timed_out() {
+            self.sequence.clear();
+        } else {
+            // Runs restart where the one before breaks, which is decided by
+            // the char after the break
+            let restart = self.sequence
+                              .iter()
+                              .flat_map(|m| iter::once(m.start).chain(iter::once(m.end)))
+                              .filter(|&i| i + 2 <= unchanged)
+                              .max()
+                              .unwrap_or(0);
+            rescan(&mut self.sequence, password, length, restart, restart, &sequence_match);
+        }
+
+        if context.timed_out() {
+            self.years.clear();
+        } else {
+            // Years left to right without overlaps, only the first unless
+            // matching as zxcvbn does
+            let from = unchanged.saturating_sub(3);
+            let resume = self.years
+                             .iter()
+                             .filter(|m| m.start < from)
+                             .map(|m| m.end + 1)
+                             .max()
+                             .unwrap_or(0);
+            let years: &Matcher<'_> = if context.compat {
+                &recent_years_match
+            } else {
+                &recent_year_match
+            };
+            rescan(&mut self.years, password, length, from, cmp::max(from, resume), years);
+            if !context.compat {
+                self.years.truncate(1);
+            }
+        }
+
+        if context.timed_out() {
+            self.repeat.clear();
+        } else {
+            self.repeat = repeat_match_with(password, estimator, context);
+        }
+
+        if context.timed_out() {
+            self.walks.clear();
+            self.spatial.clear();
+        } else {
+            // zxcvbn has no tolerant walks
+            let slips = if context.compat { 0 } else { estimator.spatial_slips };
+            let passes = spatial_passes(&estimator.keyboards, slips);
+            self.walks.resize(passes.len(), Vec::new());
+            for (walks, &(name, graph, slips)) in self.walks.iter_mut().zip(passes.iter()) {
+                // A walk starts where the one before ends, which is decided
+                // by the chars up to `slips` after its end
+                let restart = walks.iter()
+                                   .flat_map(|m| iter::once(m.start).chain(iter::once(m.end + 1)))
+                                   .filter(|&i| i + slips < unchanged)
+                                   .max()
+                                   .unwrap_or(0);
+                rescan(walks, password, length, restart, restart,
+                       &|p| spatial_helper(p, name, graph, slips));
+            }
+            self.spatial = merge_walks(&passes, &self.walks);
+        }
+
+        if context.compat {
+            if context.timed_out() {
+                self.dates.clear();
+            } else {
+                // Dates are at most 10 chars, so those starting 9 chars
+                // before the change can only be inside ones starting 9 chars
+                // before that
+                let from = unchanged.saturating_sub(9);
+                let date = |p: &str| zxcvbn_date_match(p, context.reference_year);
+                rescan(&mut self.dates, password, length, from, from.saturating_sub(9), &date);
+            }
+        } else {
+            #[cfg(feature = "breach")]
+            {
+                if context.timed_out() {
+                    self.breach.clear();
+                } else if changed {
+                    self.breach = match estimator.breaches {
+                        Some(ref breaches) => breach_match(password, breaches),
+                        None => Vec::new(),
+                    };
+                }
+            }
+            #[cfg(feature = "blocklist")]
+            {
+                if context.timed_out() {
+                    self.blocklist.clear();
+                } else if changed {
+                    self.blocklist = match estimator.blocklist {
+                        Some(ref blocklist) => blocklist_match(password, blocklist),
+                        None => Vec::new(),
+                    };
+                }
+            }
+        }
+        // Matchers were skipped so everything is rematched next time
+        if context.timed_out.get() {
+            self.length = 0;
+        }
+    }
+
+    /// The matches in the order the matchers run
+    pub fn matches(&self) -> Vec<BaseMatch> {
+        let mut result = self.l33t.clone();
+        for matches in [&self.sequence, &self.years, &self.repeat, &self.spatial,
+                        &self.dates, &self.breach, &self.blocklist].iter() {
+            result.extend(matches.iter().cloned());
+        }
+        result
+    }
+}
+
+/// Keeps the matches starting before `from` and adds those from `from` on
+/// found scanning the password from `scan_from`
+fn rescan(matches: &mut Vec<BaseMatch>,
+          password: &str,
+          length: usize,
+          from: usize,
+          scan_from: usize,
+          matcher: &Matcher<'_>) {
+    matches.retain(|m| m.start < from);
+    matches.extend(matcher(char_slice(password, scan_from, length))
+                       .into_iter()
+                       .map(|m| shifted(m, scan_from))
+                       .filter(|m| m.start >= from));
+}
+
+#[test]
+fn global_rematch_test() {
+    let mut compat = Estimator::new();
+    compat.compat = true;
+    let mut tolerant = Estimator::new();
+    tolerant.spatial_slips = 2;
+    tolerant.l33t_budget = 16;
+    let inputs = ["jsm1th"];
+    let typed = ["p4ssw0rd1p4ss!", "abczyx1357qwerty", "qwwertyasdfgghjkl zxcvbnm,",
+                 "1/1/91x2015_06_04x19201988", "aaaabbbbaaaabbbbc", "mNbVcvBnM,.?",
+                 "h0rse8attery|-|e11o", "ph1l0vv0rld2019", "7r0ub4d0r&3"];
+    for estimator in [Estimator::new(), compat, tolerant].iter() {
+        let mut global = GlobalMatches::default();
+        let mut previous = Vec::new();
+        for word in typed.iter() {
+            let chars = word.chars().collect::<Vec<char>>();
+            // Typed, deleted back to the middle, and edited in the middle
+            let mut edits = (1..chars.len() + 1).map(|n| chars[..n].to_vec())
+                                                .collect::<Vec<Vec<char>>>();
+            edits.push(chars[..chars.len() - 1].to_vec());
+            edits.push(chars[..chars.len() / 2].to_vec());
+            let mut edited = chars.clone();
+            edited[chars.len() / 3] = 'x';
+            edits.push(edited);
+            for password in edits.iter() {
+                let unchanged = previous.iter()
+                                        .zip(password.iter())
+                                        .take_while(|&(a, b)| a == b)
+                                        .count();
+                let password = password.iter().collect::<String>();
+                let context = MatchContext::with_user_inputs(estimator, &inputs);
+                global.rematch(&password, unchanged, estimator, &context);
+                let fresh = MatchContext::with_user_inputs(estimator, &inputs);
+                let expected = global_match_with(&password, estimator, &fresh);
+                let matches = global.matches();
+                assert_eq!(matches.len(), expected.len(), "{}", password);
+                for (m, e) in matches.iter().zip(expected.iter()) {
+                    assert!(m == e && m.pattern == e.pattern && m.token == e.token &&
+                            m.data == e.data, "{} {:?} {:?}", password, m, e);
+                }
+                assert_eq!(context.l33t_budget.exhausted(), fresh.l33t_budget.exhausted());
+                previous = password.chars().collect();
+            }
+        }
+    }
+}
+
+#[test]
+fn time_budget_test() {
+    let mut estimator = Estimator::new();
+    estimator.time_budget = Some(Duration::from_secs(0));
+    let result = estimator.estimate("kq8#mz!Qp2@xL9vR4wT7yZ");
+    assert!(result.truncated);
+    assert_eq!(result.score, Some(::result::PasswordScore::Weak));
+}
+
+#[test]
+fn clock_test() {
+    use clock::{FixedClock, SteppingClock};
+
+    static STEPPING: SteppingClock = SteppingClock::new();
+    static YEAR_1995: FixedClock = FixedClock { year: 1995 };
+    static YEAR_2017: FixedClock = FixedClock { year: 2017 };
+
+    let mut estimator = Estimator::new();
+    estimator.clock = &STEPPING;
+    estimator.time_budget = Some(Duration::from_millis(3));
+    let result = estimator.estimate("kq8#mz!Qp2@xL9vR4wT7yZ");
+    assert!(result.truncated);
+    assert!(result.calculation_time > 3);
+
+    // Recent years are at least 20 years of guesses from the reference year
+    estimator = Estimator::new();
+    estimator.clock = &YEAR_1995;
+    assert_eq!(estimator.estimate("1990").guesses, 20);
+    estimator.clock = &YEAR_2017;
+    assert_eq!(estimator.estimate("1990").guesses, 27);
+}
+
+#[test]
+fn user_inputs_test() {
+    let estimator = Estimator::new();
+    let without = estimator.estimate("jsmith1987");
+    let with = estimator.estimate_with_inputs("jsmith1987", &["JSmith", ""]);
+    assert!(with.guesses < without.guesses);
+    let m = &with.sequence[0];
+    assert_eq!((m.start, m.end), (0, 5));
+    match m.data {
+        MatchData::Dictionary { ref dictionary_name, rank, .. } => {
+            assert_eq!(dictionary_name, "User inputs");
+            assert_eq!(rank, 1);
+        }
+        _ => panic!("Expected a dictionary match"),
+    }
+}
+
 
 fn dictionary_match(password: &str, 
                     dictionary_name: &str, 
-                    dictionary: &[&str]) -> Vec<BaseMatch> {
+                    dictionary: &Dictionary) -> Vec<BaseMatch> {
 
     let mut matches: Vec<BaseMatch> = Vec::new();
-    let lower = password.to_lowercase();
-    for i in 0..password.len() {
-        for j in i..password.len() {
-            let slice = &lower[i..j + 1];
-            if let Some(pass) = dictionary.iter().position(|&x| x == slice) {
+    // Lowercase char by char so indices into lower match the password
+    let lower = password.chars().map(lower_char).collect::<String>();
+    let offsets = lower.char_indices()
+                       .map(|(i, _)| i)
+                       .chain(Some(lower.len()))
+                       .collect::<Vec<usize>>();
+    let length = offsets.len() - 1;
+    for i in 0..length {
+        for j in i..cmp::min(i + dictionary.longest(), length) {
+            let slice = &lower[offsets[i]..offsets[j + 1]];
+            if let Some(rank) = dictionary.rank(slice) {
                 let dict = MatchData::Dictionary {
                     matched_word: slice.to_string(),
-                    rank: pass + 1,
+                    rank,
                     dictionary_name: dictionary_name.to_string(),
                     reversed: false,
                     l33t: None,
@@ -180,7 +670,7 @@ fn dictionary_match(password: &str,
                     pattern: String::from("Dictionary"),
                     start: i,
                     end: j,
-                    token: password[i..j+1].to_string(),
+                    token: char_slice(password, i, j + 1).to_string(),
                     data: dict,
                 });
             }
@@ -192,7 +682,7 @@ fn dictionary_match(password: &str,
 
 #[test]
 fn dictionary_test() {
-    let m = dictionary_match("password", "test", &["pass", "password", "dave"]);
+    let m = dictionary_match("password", "test", &Dictionary::new(&["pass", "password", "dave"]));
     assert_eq!(m.len(), 2);
     for temp in m.iter() {
         match temp.data {
@@ -203,9 +693,30 @@ fn dictionary_test() {
     }
 }
 
+#[test]
+fn matches_from_all_dicts_test() {
+    // Holds for whichever dictionaries the features compile in
+    let m = matches_from_all_dicts("password", &dictionary_match);
+    for &(name, dictionary) in DICTIONARIES.iter() {
+        let found = m.iter().any(|x| match x.data {
+            MatchData::Dictionary { ref dictionary_name, .. } => {
+                dictionary_name == name && x.token == "password"
+            }
+            _ => false,
+        });
+        assert_eq!(found, dictionary.rank("password").is_some(), "{}", name);
+    }
+    assert!(m.iter().all(|x| match x.data {
+        MatchData::Dictionary { ref dictionary_name, .. } => {
+            DICTIONARIES.iter().any(|&(name, _)| name == dictionary_name)
+        }
+        _ => false,
+    }));
+}
+
 pub fn reverse_dictionary_match(password: &str,
                                 dictionary_name: &str,
-                                dictionary: &[&str]) -> Vec<BaseMatch> {
+                                dictionary: &Dictionary) -> Vec<BaseMatch> {
     let length = password.chars().count();
     let reversed = password.chars().rev().collect::<String>();
 
@@ -230,7 +741,7 @@ pub fn reverse_dictionary_match(password: &str,
 
 #[test]
 fn reverse_test() {
-    let m = reverse_dictionary_match("drowssap", "test", &["password"]);
+    let m = reverse_dictionary_match("drowssap", "test", &Dictionary::new(&["password"]));
     assert_eq!(m.len(), 1);
 
     let ref temp = m[0];
@@ -248,130 +759,419 @@ fn reverse_test() {
 }
 
 
-fn replace_single_l33t_char(c: &char) -> char {
-    let res = L33T_TABLE.get(c);
-    match res {
-        Some(s) => {
-            if s.chars().count() == 1 {
-                s.chars().nth(0).unwrap_or(*c)
-            } else {
-                *c
+/// Part of the password after applying l33t substitutions. Start and end are
+/// the byte range the text came from in the password. Token is set if the text
+/// was substituted for a l33t token.
+struct L33tSegment<'a> {
+    text: String,
+    start: usize,
+    end: usize,
+    token: Option<&'a str>,
+}
+
+fn starts_with_ignore_case(text: &str, token: &str) -> bool {
+    let mut text_chars = text.chars().map(lower_char);
+    token.chars().map(lower_char).all(|c| text_chars.next() == Some(c))
+}
+
+/// Replaces every token in `subs` found in the password between the byte
+/// offsets start and end with its letter, preferring the longest token where
+/// more than one could apply.
+fn apply_l33t_subs<'a>(password: &str,
+                       start: usize,
+                       end: usize,
+                       subs: &HashMap<&'a str, char>) -> Vec<L33tSegment<'a>> {
+    let password = &password[..end];
+    let mut result = Vec::new();
+    let mut pos = start;
+    while pos < password.len() {
+        let rest = &password[pos..];
+        let token = subs.keys()
+                        .filter(|t| starts_with_ignore_case(rest, t))
+                        .max_by_key(|t| t.chars().count());
+        let (text, len, token) = match token {
+            Some(t) => {
+                // Tokens match case insensitively so find the length in the
+                // password rather than in the token
+                let count = t.chars().count();
+                let len = rest.char_indices()
+                              .nth(count)
+                              .map(|(i, _)| i)
+                              .unwrap_or_else(|| rest.len());
+                (subs[t].to_string(), len, Some(*t))
             }
-        }
-        None => *c,
+            None => {
+                let c = rest.chars().next().unwrap();
+                (lower_char(c).to_string(), c.len_utf8(), None)
+            }
+        };
+        result.push(L33tSegment {
+            text,
+            start: pos,
+            end: pos + len,
+            token,
+        });
+        pos += len;
     }
+    result
 }
 
-
 fn check_l33t_sub(password: &str, 
-                  sub: &str, 
+                  segments: &[L33tSegment],
                   dictionary_name: &str,
-                  dictionary: &[&str]) -> Vec<BaseMatch> {
-    let mut tm = dictionary_match(sub, dictionary_name, dictionary);
+                  dictionary: &Dictionary) -> Vec<BaseMatch> {
+    let mut sub = String::new();
+    let mut owners: Vec<usize> = Vec::new();
+    for (i, segment) in segments.iter().enumerate() {
+        sub.push_str(&segment.text);
+        owners.extend(segment.text.chars().map(|_| i));
+    }
+
+    let mut tm = dictionary_match(&sub, dictionary_name, dictionary);
     for m in tm.iter_mut() {
-        m.token = password[m.start..(m.end + 1)].to_string();
-        match m.data {
-            MatchData::Dictionary{ref mut l33t, ref matched_word, ..} => {
-                let mut tmap:HashMap<char, String> = HashMap::new();
-                for (k, v) in m.token.chars().zip(matched_word.chars()) {
-                    if k == v {
-                        continue;
-                    }
-                    if tmap.contains_key(&k) {
-                        let c_as_s = v.to_string();
-                        let ref mut value = tmap.get_mut(&k).unwrap();
-                        if false == value.contains(v) {
-                            value.push_str(c_as_s.as_ref());
-                        }
-                    } else {
-                        tmap.insert(k, v.to_string());
-                    }
+        let covered = &segments[owners[m.start]..(owners[m.end] + 1)];
+        let (start, end) = (covered[0].start, covered[covered.len() - 1].end);
+        m.start = char_index(password, start);
+        m.end = char_index(password, end) - 1;
+        m.token = password[start..end].to_string();
+
+        let mut tmap: HashMap<String, String> = HashMap::new();
+        let mut spans: Vec<L33tSpan> = Vec::new();
+        for segment in covered.iter() {
+            if let Some(token) = segment.token {
+                let letter = segment.text.chars().next().unwrap();
+                let value = tmap.entry(token.to_string()).or_default();
+                if !value.contains(letter) {
+                    value.push(letter);
                 }
-                *l33t = Some(L33tData { l33t_subs: tmap });
-            },
-            _ => {},
+                spans.push(L33tSpan {
+                    start: char_index(password, segment.start),
+                    end: char_index(password, segment.end) - 1,
+                    token: password[segment.start..segment.end].to_string(),
+                    letter,
+                });
+            }
+        }
+        if let MatchData::Dictionary{ref mut l33t, ..} = m.data {
+            *l33t = Some(L33tData { l33t_subs: tmap, spans });
         }
     }
+    // Matches which don't include a substitution are found by the plain
+    // dictionary matcher
+    tm.retain(|m| match m.data {
+        MatchData::Dictionary{ l33t: Some(ref data), ..} => !data.spans.is_empty(),
+        _ => false,
+    });
     tm
 }
 
-/// l33t match assumes that a symbol which can mean multiple letters will only
-/// be used for one of those letters during a match.
-/// Behaviour slightly differs from dropbox on this currently
+/// Finds dictionary words written with the default l33t substitutions. As in
+/// dropbox's zxcvbn, a token that can stand for several letters stands for the
+/// same one everywhere it appears in a match.
 pub fn l33t_match(password: &str, 
                   dictionary_name: &str,
-                  dictionary: &[&str]) -> Vec<BaseMatch> {
+                  dictionary: &Dictionary) -> Vec<BaseMatch> {
+    l33t_match_with_table(password, dictionary_name, dictionary, &DEFAULT_L33T_TABLE)
+}
 
-    let mut matches: Vec<BaseMatch> = Vec::new();
+/// l33t matching using a custom substitution table. Single character tokens
+/// with one possible letter are always substituted. For the remaining tokens
+/// every choice of letter is tried, and multi-character tokens are also tried
+/// unsubstituted as they may be ordinary text (e.g. "ph"). At most
+/// `DEFAULT_L33T_BUDGET` substituted variants are checked.
+pub fn l33t_match_with_table(password: &str,
+                             dictionary_name: &str,
+                             dictionary: &Dictionary,
+                             table: &HashMap<String, String>) -> Vec<BaseMatch> {
+    let budget = L33tBudget::new(DEFAULT_L33T_BUDGET);
+    l33t_match_bounded(password, dictionary_name, dictionary, table, &budget)
+}
 
-    // First we do all the simple subs. Then go through permutations
-    let partial_sub: String = password.chars()
-                                      .map(|c| replace_single_l33t_char(&c))
-                                      .collect();
+/// Default limit on the substituted variants checked by the l33t matcher
+pub const DEFAULT_L33T_BUDGET: usize = 256;
+
+/// Limits the number of substituted variants of a password checked against
+/// the dictionaries. One budget is shared by every dictionary and recursive
+/// match for a password so the total work is bounded.
+#[derive(Debug)]
+pub struct L33tBudget {
+    remaining: Cell<usize>,
+    exhausted: Cell<bool>,
+    /// Clock reading after which no more variants are checked
+    deadline: Option<(&'static dyn Clock, Duration)>,
+}
 
-    let remaining_l33ts = partial_sub.chars()
-                                     .fold(0u32, |acc, c| acc + L33T_TABLE.contains_key(&c) as u32);
+impl L33tBudget {
+    pub fn new(limit: usize) -> L33tBudget {
+        L33tBudget {
+            remaining: Cell::new(limit),
+            exhausted: Cell::new(false),
+            deadline: None,
+        }
+    }
 
-    if remaining_l33ts == 0 && partial_sub != password {
+    /// A budget which also stops once the clock reaches the deadline
+    pub(crate) fn with_deadline(limit: usize,
+                                clock: &'static dyn Clock,
+                                deadline: Option<Duration>) -> L33tBudget {
+        L33tBudget {
+            deadline: deadline.map(|d| (clock, d)),
+            ..L33tBudget::new(limit)
+        }
+    }
 
-        let mut tm = check_l33t_sub(password, partial_sub.as_ref(), 
-                                    dictionary_name, dictionary);
-        matches.append(&mut tm);
+    /// True if a variant was skipped because the budget ran out
+    pub fn exhausted(&self) -> bool {
+        self.exhausted.get()
+    }
 
-    } else if remaining_l33ts > 0 {
-        let subtable = L33T_TABLE.iter()
-                                 .filter(|&(k, _)| partial_sub.contains(*k))
-                                 .map(|(k, v)| (*k, *v))
-                                 .collect::<Vec<(char, &str)>>();
+    fn take(&self) -> bool {
+        if let Some((clock, deadline)) = self.deadline {
+            if clock.now() >= deadline {
+                return false;
+            }
+        }
+        let remaining = self.remaining.get();
+        if remaining == 0 {
+            self.exhausted.set(true);
+            false
+        } else {
+            self.remaining.set(remaining - 1);
+            true
+        }
+    }
 
-        let sizes = subtable.iter()
-                            .map(|&(_, v)| (*v).chars().count())
-                            .collect::<Vec<usize>>();
+    /// Takes `count` variants at once if that many remain, for a region
+    /// whose matches are already known
+    fn take_all(&self, count: usize) -> bool {
+        if count == 0 {
+            return true;
+        }
+        if let Some((clock, deadline)) = self.deadline {
+            if clock.now() >= deadline {
+                return false;
+            }
+        }
+        let remaining = self.remaining.get();
+        if remaining < count {
+            false
+        } else {
+            self.remaining.set(remaining - count);
+            true
+        }
+    }
+}
 
-        let mut current = 0;
-        let mut indexes: Vec<usize> = vec![0; sizes.len()];
-        while current != sizes.len() {
+/// Matches found in a region by the l33t matcher, relative to the region, and
+/// the number of variants checked to find them
+type L33tRegion = (usize, Vec<BaseMatch>);
+
+/// Regions enumerated by the l33t matcher keyed by dictionary and the region's
+/// text, which are all its matches depend on. A session replays the regions
+/// of the previous password an edit doesn't reach rather than enumerating
+/// them again.
+#[derive(Default)]
+pub(crate) struct L33tRegions {
+    previous: HashMap<(String, String), L33tRegion>,
+    current: RefCell<HashMap<(String, String), L33tRegion>>,
+}
 
-            let sub = subtable.iter()
-                              .enumerate()
-                              .map(|(i, &(k, v))| (v.chars().nth(indexes[i]).unwrap(), k))
-                              .collect::<HashMap<char, char>>();
-            
-            if sub.len() == sizes.len() {
-                let sub = sub.iter()
-                             .map(|(k, v)| (*v, *k))
-                             .collect::<HashMap<char, char>>();
-
-                let full_sub = partial_sub.chars()
-                                          .map(|c| {
-                                              match sub.get(&c) {
-                                                  Some(v) => *v,
-                                                  None => c,
-                                              }
-                                          })
-                                          .collect::<String>();
-                let mut tm = check_l33t_sub(password, full_sub.as_ref(), 
-                                            dictionary_name, dictionary);
-                matches.append(&mut tm);
+impl L33tRegions {
+    /// Keeps the regions of the password just matched for the next one
+    fn advance(&mut self) {
+        self.previous = self.current.get_mut().drain().collect();
+    }
+}
+
+/// The match moved `offset` chars on, for a match found in part of a password
+fn shifted(mut m: BaseMatch, offset: usize) -> BaseMatch {
+    m.start += offset;
+    m.end += offset;
+    if let MatchData::Dictionary{ l33t: Some(ref mut data), ..} = m.data {
+        for span in data.spans.iter_mut() {
+            span.start += offset;
+            span.end += offset;
+        }
+    }
+    m
+}
+
+/// l33t matching limited by a budget. Substitutions are only enumerated over
+/// the parts of the password close enough to a l33t token for a dictionary
+/// word to include it, and separate parts are enumerated independently using
+/// only the tokens they contain.
+pub fn l33t_match_bounded(password: &str,
+                          dictionary_name: &str,
+                          dictionary: &Dictionary,
+                          table: &HashMap<String, String>,
+                          budget: &L33tBudget) -> Vec<BaseMatch> {
+    l33t_match_replaying(password, dictionary_name, dictionary, table, budget,
+                         &L33tRegions::default())
+}
+
+/// Bounded l33t matching which replays regions enumerated for the previous
+/// password. A known region is only replayed if the budget covers all its
+/// variants, otherwise it's enumerated to run out at the same variant.
+fn l33t_match_replaying(password: &str,
+                        dictionary_name: &str,
+                        dictionary: &Dictionary,
+                        table: &HashMap<String, String>,
+                        budget: &L33tBudget,
+                        known: &L33tRegions) -> Vec<BaseMatch> {
+
+    let mut matches: Vec<BaseMatch> = Vec::new();
+
+    // Occurrences of l33t tokens as char ranges in the password
+    let offsets = password.char_indices()
+                          .map(|(i, _)| i)
+                          .chain(Some(password.len()))
+                          .collect::<Vec<usize>>();
+    let mut occurrences: Vec<(usize, usize)> = Vec::new();
+    for (k, v) in table.iter() {
+        if k.is_empty() || v.is_empty() {
+            continue;
+        }
+        let length = k.chars().count();
+        for (i, &offset) in offsets[..offsets.len() - 1].iter().enumerate() {
+            if starts_with_ignore_case(&password[offset..], k) {
+                occurrences.push((i, cmp::min(i + length, offsets.len() - 1)));
             }
+        }
+    }
+    if occurrences.is_empty() {
+        return matches;
+    }
+    occurrences.sort();
+
+    let longest_word = dictionary.longest();
+    let longest_token = occurrences.iter()
+                                   .map(|&(start, end)| end - start)
+                                   .max()
+                                   .unwrap_or(1);
+    let reach = longest_word.saturating_sub(1) * longest_token;
+
+    let mut regions: Vec<(usize, usize)> = Vec::new();
+    for &(start, end) in occurrences.iter() {
+        let start = start.saturating_sub(reach);
+        let end = cmp::min(end + reach, offsets.len() - 1);
+        match regions.last_mut() {
+            Some(last) if start <= last.1 => last.1 = cmp::max(last.1, end),
+            _ => regions.push((start, end)),
+        }
+    }
 
-            indexes[current] += 1;
-            if indexes[current] == sizes[current] {
-                indexes[current] = 0;
-                current += 1;
-                if current < sizes.len() {
-                    indexes[current] += 1;
-                }
+    for &(start, end) in regions.iter() {
+        let region = &password[offsets[start]..offsets[end]];
+        let key = (dictionary_name.to_string(), region.to_string());
+        let (found, variants) = match known.previous.get(&key) {
+            Some(&(variants, ref found)) if budget.take_all(variants) => {
+                (found.clone(), Some(variants))
             }
+            _ => {
+                let region_table = table.iter()
+                                        .filter(|&(k, v)| {
+                                            !k.is_empty() && !v.is_empty() &&
+                                            region.char_indices()
+                                                  .any(|(i, _)| starts_with_ignore_case(&region[i..], k))
+                                        })
+                                        .collect::<Vec<_>>();
+                let remaining = budget.remaining.get();
+                let mut found = Vec::new();
+                let complete = l33t_region_match(region,
+                                                 &region_table,
+                                                 dictionary_name,
+                                                 dictionary,
+                                                 budget,
+                                                 &mut found);
+                // Regions cut short by the budget or deadline aren't kept
+                (found, if complete { Some(remaining - budget.remaining.get()) } else { None })
+            }
+        };
+        matches.extend(found.iter().cloned().map(|m| shifted(m, start)));
+        if let Some(variants) = variants {
+            known.current.borrow_mut().insert(key, (variants, found));
+        }
+        if budget.exhausted() {
+            break;
         }
     }
     matches.sort();
     matches
 }
 
+/// Enumerates the substitutions in a region of a password, returning false if
+/// the budget or deadline stopped it
+fn l33t_region_match(region: &str,
+                     table: &[(&String, &String)],
+                     dictionary_name: &str,
+                     dictionary: &Dictionary,
+                     budget: &L33tBudget,
+                     matches: &mut Vec<BaseMatch>) -> bool {
+
+    let mut fixed: HashMap<&str, char> = HashMap::new();
+    let mut choices: Vec<(&str, Vec<Option<char>>)> = Vec::new();
+    for &(k, v) in table.iter() {
+        if k.chars().count() == 1 && v.chars().count() == 1 {
+            fixed.insert(k.as_str(), v.chars().next().unwrap());
+        } else {
+            let mut options = v.chars().map(Some).collect::<Vec<_>>();
+            if k.chars().count() > 1 {
+                options.push(None);
+            }
+            choices.push((k.as_str(), options));
+        }
+    }
+    // Keeps the enumeration order independent of the table's hashing
+    choices.sort();
+
+    let mut indexes: Vec<usize> = vec![0; choices.len()];
+    loop {
+        let mut subs = fixed.clone();
+        let mut letters: Vec<char> = Vec::new();
+        for (&(token, ref options), &i) in choices.iter().zip(indexes.iter()) {
+            if let Some(letter) = options[i] {
+                subs.insert(token, letter);
+                letters.push(letter);
+            }
+        }
+        let chosen = letters.len();
+        letters.sort();
+        letters.dedup();
+
+        if letters.len() == chosen && !subs.is_empty() {
+            if !budget.take() {
+                return false;
+            }
+            let segments = apply_l33t_subs(region, 0, region.len(), &subs);
+            for m in check_l33t_sub(region, &segments, dictionary_name, dictionary) {
+                let duplicate = matches.iter().any(|x| {
+                    x.start == m.start && x.end == m.end && x.data == m.data
+                });
+                if !duplicate {
+                    matches.push(m);
+                }
+            }
+        }
+
+        // Advance to the next combination of choices
+        let mut current = 0;
+        while current < indexes.len() {
+            indexes[current] += 1;
+            if indexes[current] < choices[current].1.len() {
+                break;
+            }
+            indexes[current] = 0;
+            current += 1;
+        }
+        if current == indexes.len() {
+            return true;
+        }
+    }
+}
+
 #[test]
 fn l33t_match_test() {
-    let m = l33t_match("pa$$w0rd", "t3st", &["password", "pass"]);
+    let m = l33t_match("pa$$w0rd", "t3st", &Dictionary::new(&["password", "pass"]));
     assert_eq!(m.len(), 2);
     
     for temp in m.iter() {
@@ -384,31 +1184,297 @@ fn l33t_match_test() {
         }
     }
 
-    let m = l33t_match("!llus1on", "t3st", &["illusion"]);
+    let m = l33t_match("!llus1on", "t3st", &Dictionary::new(&["illusion"]));
     assert_eq!(m.len(), 0);
 }
 
+#[test]
+fn multi_char_l33t_match_test() {
+    let m = l33t_match("x|-|elloPHone", "t3st", &Dictionary::new(&["hello", "phone", "fone"]));
+    assert_eq!(m.len(), 2);
+    assert_eq!(m[0].token, "|-|ello");
+    assert_eq!((m[0].start, m[0].end), (1, 7));
+    match m[0].data {
+        MatchData::Dictionary{ref matched_word, l33t: Some(ref l33t), ..} => {
+            assert_eq!(matched_word, "hello");
+            assert_eq!(l33t.l33t_subs.get("|-|"), Some(&String::from("h")));
+            assert_eq!(l33t.spans, vec![L33tSpan {
+                start: 1,
+                end: 3,
+                token: String::from("|-|"),
+                letter: 'h',
+            }]);
+        },
+        _ => panic!("Expected a l33t dictionary match"),
+    }
+    // "PHone" is only a l33t match for "fone", "phone" is a plain match
+    assert_eq!(m[1].token, "PHone");
+    match m[1].data {
+        MatchData::Dictionary{ref matched_word, l33t: Some(ref l33t), ..} => {
+            assert_eq!(matched_word, "fone");
+            assert_eq!(l33t.spans[0].token, "PH");
+        },
+        _ => panic!("Expected a l33t dictionary match"),
+    }
+
+    let mut table = HashMap::new();
+    table.insert(String::from("><"), String::from("x"));
+    let m = l33t_match_with_table("><-ray", "t3st", &Dictionary::new(&["x-ray"]), &table);
+    assert_eq!(m.len(), 1);
+    assert_eq!(m[0].token, "><-ray");
+    assert!(l33t_match("><-ray", "t3st", &Dictionary::new(&["x-ray"])).is_empty());
+}
+
+#[test]
+fn l33t_lowercase_test() {
+    // 'İ' lowercases to 'i' and a combining dot. Only the 'i' is kept, as for
+    // plain dictionary matches, so the l33t text lines up with the password.
+    let dictionary = Dictionary::new(&["internet"]);
+    let m = l33t_match("xİnt3rn3t", "t3st", &dictionary);
+    assert_eq!(m.len(), 1);
+    assert_eq!((m[0].start, m[0].end), (1, 8));
+    assert_eq!(m[0].token, "İnt3rn3t");
+    let m = dictionary_match("xİnternet", "t3st", &dictionary);
+    assert_eq!((m[0].start, m[0].end), (1, 8));
+}
+
+#[test]
+fn bounded_l33t_match_test() {
+    // Each of '1', '!' and '7' has two possible letters. Words far apart are
+    // enumerated separately so only the tokens near each are combined.
+    let password = "!177!3xxxxxxxxxxxxxxxxxxxx1llu5i0n";
+    let budget = L33tBudget::new(DEFAULT_L33T_BUDGET);
+    let m = l33t_match_bounded(password, "t3st", &Dictionary::new(&["illusion", "little"]),
+                               &DEFAULT_L33T_TABLE, &budget);
+    assert_eq!(m.len(), 2);
+    assert_eq!(m[0].token, "!177!3");
+    assert_eq!(m[1].token, "1llu5i0n");
+    assert!(!budget.exhausted());
+
+    let budget = L33tBudget::new(1);
+    let m = l33t_match_bounded(password, "t3st", &Dictionary::new(&["illusion", "little"]),
+                               &DEFAULT_L33T_TABLE, &budget);
+    assert!(m.len() < 2);
+    assert!(budget.exhausted());
+}
+
+#[test]
+fn l33t_replay_test() {
+    let dictionary = Dictionary::new(&["smith"]);
+    let mut known = L33tRegions::default();
+    let budget = L33tBudget::new(DEFAULT_L33T_BUDGET);
+    let expected = l33t_match_replaying("5m17h", "test", &dictionary, &DEFAULT_L33T_TABLE,
+                                        &budget, &known);
+    let variants = DEFAULT_L33T_BUDGET - budget.remaining.get();
+    assert_eq!(expected.len(), 1);
+    known.advance();
+
+    // A known region costs the same variants without enumerating them
+    let budget = L33tBudget::new(DEFAULT_L33T_BUDGET);
+    let matches = l33t_match_replaying("5m17h", "test", &dictionary, &DEFAULT_L33T_TABLE,
+                                       &budget, &known);
+    assert_eq!(matches[0].data, expected[0].data);
+    assert_eq!(DEFAULT_L33T_BUDGET - budget.remaining.get(), variants);
+
+    // Without the budget for all of them it's enumerated until it runs out
+    known.advance();
+    let budget = L33tBudget::new(variants - 1);
+    let matches = l33t_match_replaying("5m17h", "test", &dictionary, &DEFAULT_L33T_TABLE,
+                                       &budget, &known);
+    let bounded = l33t_match_bounded("5m17h", "test", &dictionary, &DEFAULT_L33T_TABLE,
+                                     &L33tBudget::new(variants - 1));
+    assert_eq!(matches.len(), bounded.len());
+    assert!(budget.exhausted());
+}
+
+#[test]
+fn l33t_match_deadline_test() {
+    static CLOCK: ::clock::SteppingClock = ::clock::SteppingClock::new();
+    let password = "!177!3xxxxxxxxxxxxxxxxxxxx1llu5i0n";
+    let dictionary = Dictionary::new(&["illusion", "little"]);
+    let unbounded = L33tBudget::new(DEFAULT_L33T_BUDGET);
+    l33t_match_bounded(password, "t3st", &dictionary, &DEFAULT_L33T_TABLE, &unbounded);
+    assert!(unbounded.remaining.get() < DEFAULT_L33T_BUDGET - 3);
+
+    // The clock passes the deadline on its fourth reading, so only three
+    // variants are checked
+    let budget = L33tBudget::with_deadline(DEFAULT_L33T_BUDGET, &CLOCK, Some(Duration::from_millis(3)));
+    l33t_match_bounded(password, "t3st", &dictionary, &DEFAULT_L33T_TABLE, &budget);
+    assert_eq!(budget.remaining.get(), DEFAULT_L33T_BUDGET - 3);
+    assert!(!budget.exhausted());
+}
+
+/// zxcvbn 4.4's l33t table, each letter with the characters that may stand
+/// for it
+static ZXCVBN_L33T_TABLE: [(char, &str); 12] = [
+    ('a', "4@"), ('b', "8"), ('c', "({[<"), ('e', "3"), ('g', "69"), ('i', "1!|"),
+    ('l', "1|7"), ('o', "0"), ('s', "$5"), ('t', "+7"), ('x', "%"), ('z', "2"),
+];
+
+/// The substitutions zxcvbn 4.4 tries for a password, as pairs of the l33t
+/// character and the letter it stands for. Each character stands for a single
+/// letter and each letter is stood for by at most one character.
+fn zxcvbn_l33t_subs(password: &str) -> Vec<Vec<(char, char)>> {
+    let mut subs: Vec<Vec<(char, char)>> = vec![Vec::new()];
+    for &(letter, l33t_chars) in ZXCVBN_L33T_TABLE.iter() {
+        let relevant = l33t_chars.chars().filter(|c| password.contains(*c));
+        let mut next: Vec<Vec<(char, char)>> = Vec::new();
+        for c in relevant {
+            for sub in subs.iter() {
+                // A character already standing for another letter is tried
+                // as both
+                let mut extended = sub.clone();
+                if let Some(i) = sub.iter().position(|&(l33t, _)| l33t == c) {
+                    next.push(sub.clone());
+                    extended.remove(i);
+                }
+                extended.push((c, letter));
+                next.push(extended);
+            }
+        }
+        if next.is_empty() {
+            continue;
+        }
+        subs.clear();
+        for sub in next {
+            let mut sorted = sub.clone();
+            sorted.sort();
+            let seen = subs.iter().any(|s| {
+                let mut s = s.clone();
+                s.sort();
+                s == sorted
+            });
+            if !seen {
+                subs.push(sub);
+            }
+        }
+    }
+    subs
+}
+
+/// l33t matching as done by zxcvbn 4.4. The password is matched with each
+/// substitution from `zxcvbn_l33t_subs` applied, keeping words of two or more
+/// characters which needed one.
+pub fn zxcvbn_l33t_match(password: &str,
+                         dictionary_name: &str,
+                         dictionary: &Dictionary) -> Vec<BaseMatch> {
+    let mut matches: Vec<BaseMatch> = Vec::new();
+    for sub in zxcvbn_l33t_subs(password) {
+        if sub.is_empty() {
+            break;
+        }
+        let subbed = password.chars()
+                             .map(|c| sub.iter().find(|s| s.0 == c).map_or(c, |s| s.1))
+                             .collect::<String>();
+        for mut m in dictionary_match(&subbed, dictionary_name, dictionary) {
+            let token = char_slice(password, m.start, m.end + 1).to_string();
+            if token.chars().count() < 2 {
+                continue;
+            }
+            let used = sub.iter()
+                          .filter(|s| token.contains(s.0))
+                          .cloned()
+                          .collect::<Vec<(char, char)>>();
+            let start = m.start;
+            if let MatchData::Dictionary { ref matched_word, ref mut l33t, .. } = m.data {
+                if token.chars().map(lower_char).collect::<String>() == *matched_word {
+                    continue;
+                }
+                let spans = token.chars()
+                                 .enumerate()
+                                 .filter_map(|(i, c)| {
+                                     used.iter().find(|s| s.0 == c).map(|s| L33tSpan {
+                                         start: start + i,
+                                         end: start + i,
+                                         token: c.to_string(),
+                                         letter: s.1,
+                                     })
+                                 })
+                                 .collect();
+                *l33t = Some(L33tData {
+                    l33t_subs: used.iter().map(|s| (s.0.to_string(), s.1.to_string())).collect(),
+                    spans,
+                });
+            }
+            m.token = token;
+            // Substitutions that agree on the token find the same match
+            if !matches.iter().any(|x| x == &m && x.data == m.data) {
+                matches.push(m);
+            }
+        }
+    }
+    matches.sort();
+    matches
+}
+
+#[test]
+fn zxcvbn_l33t_match_test() {
+    // '1' may stand for 'i' or 'l', but never both in one match
+    let subs = zxcvbn_l33t_subs("1!");
+    assert_eq!(subs.len(), 3);
+    assert!(subs.contains(&vec![('!', 'i'), ('1', 'l')]));
+
+    let m = zxcvbn_l33t_match("p4$$w0rd", "t3st", &Dictionary::new(&["password", "pass", "a"]));
+    assert_eq!(m.len(), 2);
+    match m[1].data {
+        MatchData::Dictionary{ref matched_word, l33t: Some(ref l33t), ..} => {
+            assert_eq!(matched_word, "password");
+            assert_eq!(l33t.l33t_subs.len(), 3);
+            assert_eq!(l33t.spans.len(), 4);
+        },
+        _ => panic!("Expected a l33t dictionary match"),
+    }
+    assert!(zxcvbn_l33t_match("!llus1on", "t3st", &Dictionary::new(&["illusion"])).is_empty());
+    assert!(zxcvbn_l33t_match("password", "t3st", &Dictionary::new(&["password"])).is_empty());
+}
+
+/// An alphabet that sequences can be drawn from. Characters between first and
+/// last belong to the alphabet. Size is the number of letters, which can be
+/// less than the number of code points in the range. Extremes are the
+/// characters a sequence is most likely to start from.
+#[derive(Debug)]
+pub struct Alphabet {
+    pub name: &'static str,
+    pub first: char,
+    pub last: char,
+    pub size: u32,
+    pub extremes: &'static str,
+}
+
+/// Alphabets recognised by the sequence matcher. Sequences not contained in
+/// any of these are named "unicode" and given a space of 26.
+pub static SEQUENCE_ALPHABETS: &[Alphabet] = &[
+    Alphabet { name: "lower", first: 'a', last: 'z', size: 26, extremes: "az" },
+    Alphabet { name: "upper", first: 'A', last: 'Z', size: 26, extremes: "AZ" },
+    Alphabet { name: "digits", first: '0', last: '9', size: 10, extremes: "019" },
+    Alphabet { name: "cyrillic lower", first: 'а', last: 'я', size: 33, extremes: "ая" },
+    Alphabet { name: "cyrillic upper", first: 'А', last: 'Я', size: 33, extremes: "АЯ" },
+    Alphabet { name: "greek lower", first: 'α', last: 'ω', size: 24, extremes: "αω" },
+    Alphabet { name: "greek upper", first: 'Α', last: 'Ω', size: 24, extremes: "ΑΩ" },
+    Alphabet { name: "hebrew", first: 'א', last: 'ת', size: 22, extremes: "את" },
+    Alphabet { name: "arabic", first: 'ا', last: 'ي', size: 28, extremes: "اي" },
+    Alphabet { name: "hiragana", first: 'ぁ', last: 'ゖ', size: 46, extremes: "あん" },
+    Alphabet { name: "katakana", first: 'ァ', last: 'ヺ', size: 46, extremes: "アン" },
+];
+
+/// Finds the alphabet containing every character of the token
+pub fn sequence_alphabet(token: &str) -> Option<&'static Alphabet> {
+    SEQUENCE_ALPHABETS.iter()
+                      .find(|a| token.chars().all(|c| a.first <= c && c <= a.last))
+}
+
 fn sequence_update(token:&str, 
                    i:usize, 
                    j:usize, 
-                   delta:i16) -> Option<BaseMatch> {
+                   delta:i32) -> Option<BaseMatch> {
 
     let mut result:Option<BaseMatch> = None;
     let max_delta = 5;
     if (j as i32 - i as i32) > 1 || delta.abs() == 1 {
         if 0 < delta.abs() && delta.abs() <= max_delta {
-            let lower = Regex::new(r"^[a-z]+$").unwrap();
-            let upper = Regex::new(r"^[A-Z]+$").unwrap();
-            let digits = Regex::new(r"^\d+$").unwrap();
-            
-            let(name, space) = if lower.is_match(token) {
-                ("lower".to_string(), 26u32)
-            } else if upper.is_match(token) {
-                ("upper".to_string(), 26u32)
-            } else if digits.is_match(token) {
-                ("digits".to_string(), 10u32)
-            } else {
-                ("unicode".to_string(), 26u32)
+            let(name, space) = match sequence_alphabet(token) {
+                Some(alphabet) => (alphabet.name.to_string(), alphabet.size),
+                None => ("unicode".to_string(), 26u32),
             };
             let data = MatchData::Sequence { 
                 name:name, 
@@ -433,14 +1499,12 @@ pub fn sequence_match(password: &str) -> Vec<BaseMatch> {
     let mut matches:Vec<BaseMatch> = Vec::new();
     
     let mut i = 0;
-    let mut last_delta:Option<i16> = None;
-    let length = password.chars().count();
+    let mut last_delta:Option<i32> = None;
+    let chars = password.chars().collect::<Vec<char>>();
+    let length = chars.len();
 
     for k in 1..length {
-        let mut chars = password[(k-1)..(k+1)].chars();
-        // Prevent overflow/underflow
-        let delta = - (chars.next().unwrap() as i16) + 
-            (chars.next().unwrap() as i16);
+        let delta = chars[k] as i32 - chars[k - 1] as i32;
         if last_delta.is_none() {
             last_delta = Some(delta);
         }
@@ -449,17 +1513,15 @@ pub fn sequence_match(password: &str) -> Vec<BaseMatch> {
             _ => {},
         }
         let j = k - 1;
-        match sequence_update(&password[i..j+1], i, j, last_delta.unwrap()) {
-            Some(r) => matches.push(r),
-            None => {},
+        if let Some(r) = sequence_update(char_slice(password, i, j + 1), i, j, last_delta.unwrap()) {
+            matches.push(r);
         }
         i = j;
         last_delta = Some(delta);
     }
     if let Some(ld) = last_delta {
-        match sequence_update(&password[i..length], i, length, ld) {
-            Some(r) => matches.push(r),
-            None => {},
+        if let Some(r) = sequence_update(char_slice(password, i, length), i, length - 1, ld) {
+            matches.push(r);
         }
     }
     
@@ -475,7 +1537,7 @@ fn sequence_test() {
     let m = matches.iter().nth(0).unwrap();
     assert_eq!(m.pattern, "Sequence");
     assert_eq!(m.start, 0);
-    assert_eq!(m.end, 9);
+    assert_eq!(m.end, 8);
     assert_eq!(m.token, "123456789");
     match m.data {
         MatchData::Sequence{ref name, ref space, ref ascending} => {
@@ -487,19 +1549,71 @@ fn sequence_test() {
     }
 }
 
+#[test]
+fn char_index_test() {
+    // Positions count chars rather than bytes so they fit char_slice
+    let matches = dictionary_match("çàpassword", "test", &Dictionary::new(&["password"]));
+    assert_eq!(matches.len(), 1);
+    assert_eq!((matches[0].start, matches[0].end), (2, 9));
+    assert_eq!(matches[0].token, "password");
+    let matches = sequence_match("éabcd");
+    assert_eq!(matches.len(), 1);
+    assert_eq!((matches[0].start, matches[0].end), (1, 4));
+    assert_eq!(char_slice("éabcd", 1, 5), "abcd");
+}
+
+#[test]
+fn non_latin_sequence_test() {
+    for &(pass, name, space) in [("абвгд", "cyrillic lower", 33),
+                                 ("αβγδ", "greek lower", 24),
+                                 ("אבגד", "hebrew", 22),
+                                 ("あいうえお", "hiragana", 46)].iter() {
+        let matches = sequence_match(pass);
+        assert_eq!(matches.len(), 1, "{}", pass);
+        let m = &matches[0];
+        assert_eq!(m.token, pass);
+        assert_eq!((m.start, m.end), (0, pass.chars().count() - 1));
+        match m.data {
+            MatchData::Sequence{name: ref n, space: s, ascending} => {
+                assert_eq!(n, name);
+                assert_eq!(s, space);
+                assert!(ascending);
+            },
+            _ => panic!("Expected a sequence match"),
+        }
+    }
+}
+
+#[test]
+fn non_latin_estimate_test() {
+    let estimator = Estimator::new();
+    for pass in ["абвгд", "αβγδεζ", "パスワード", "qwéèrty"].iter() {
+        let result = estimator.estimate(pass);
+        let covered = result.sequence.iter().map(|m| m.token.as_str()).collect::<String>();
+        assert_eq!(&covered, pass);
+    }
+    let result = estimator.estimate("абвгд");
+    assert_eq!(result.sequence.len(), 1);
+    assert_eq!(result.sequence[0].pattern, "Sequence");
+    // 'а' starts the alphabet so is an extreme
+    assert_eq!(result.guesses, 4 * 5);
+}
+
 
+#[cfg(feature = "std")]
 pub fn regex_match(password: &str, 
                    regexes: HashMap<String, Regex>) -> Vec<BaseMatch> {
     let mut result: Vec<BaseMatch> = Vec::new();
     
     for (name, reg) in regexes.iter() {
-        if let Some(mat) = reg.find(password) {
+        // Ends are inclusive so an empty match has no position
+        if let Some(mat) = reg.find(password).filter(|m| !m.as_str().is_empty()) {
             let metadata = MatchData::Regex{ name:name.clone() };
             let rmatch = BaseMatch {
                 pattern: String::from("Regex"),
-                start: mat.start(),
-                end: mat.end(),
-                token: password[mat.start()..mat.end()].to_string(),
+                start: char_index(password, mat.start()),
+                end: char_index(password, mat.end()) - 1,
+                token: mat.as_str().to_string(),
                 data: metadata,
             };
             result.push(rmatch);
@@ -508,7 +1622,74 @@ pub fn regex_match(password: &str,
     result
 }
 
-fn map_ints_to_dmy(vals: &[i32; 3]) -> Option<NaiveDate> {
+#[cfg(feature = "std")]
+#[test]
+fn regex_match_test() {
+    let mut regexes = HashMap::new();
+    regexes.insert(String::from("digits"), Regex::new(r"\d+").unwrap());
+    regexes.insert(String::from("empty"), Regex::new(r"x*").unwrap());
+    let matches = regex_match("éa1987b", regexes);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].token, "1987");
+    assert_eq!((matches[0].start, matches[0].end), (2, 5));
+}
+
+/// True for a year from 1900 to 2019 written in ASCII digits, like the regex
+/// 19\d\d|200\d|201\d which zxcvbn uses for recent years
+fn is_recent_year(year: &[char]) -> bool {
+    year.iter().all(|c| c.is_ascii_digit()) &&
+        (year[..2] == ['1', '9'] || year[..3] == ['2', '0', '0'] || year[..3] == ['2', '0', '1'])
+}
+
+fn recent_year_at(chars: &[char], start: usize) -> BaseMatch {
+    BaseMatch {
+        pattern: String::from("Regex"),
+        start,
+        end: start + 3,
+        token: chars[start..start + 4].iter().collect(),
+        data: MatchData::Regex { name: String::from("recent year") },
+    }
+}
+
+/// Finds the first recent year in the password
+pub fn recent_year_match(password: &str) -> Vec<BaseMatch> {
+    let chars = password.chars().collect::<Vec<char>>();
+    match chars.windows(4).position(is_recent_year) {
+        Some(start) => vec![recent_year_at(&chars, start)],
+        None => Vec::new(),
+    }
+}
+
+/// Finds every recent year in the password from left to right without
+/// overlaps, as zxcvbn 4.4's global regex does
+pub fn recent_years_match(password: &str) -> Vec<BaseMatch> {
+    let chars = password.chars().collect::<Vec<char>>();
+    let mut result = Vec::new();
+    let mut start = 0;
+    while start + 4 <= chars.len() {
+        if is_recent_year(&chars[start..start + 4]) {
+            result.push(recent_year_at(&chars, start));
+            start += 4;
+        } else {
+            start += 1;
+        }
+    }
+    result
+}
+
+#[test]
+fn recent_year_match_test() {
+    let matches = recent_year_match("abc19876");
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].token, "1987");
+    assert_eq!((matches[0].start, matches[0].end), (3, 6));
+    assert!(recent_year_match("2020 1899 ١٩٩٠").is_empty());
+
+    let matches = recent_years_match("1990199 2001");
+    assert_eq!(matches.iter().map(|m| m.start).collect::<Vec<usize>>(), vec![0, 8]);
+}
+
+fn map_ints_to_dmy(vals: &[i32; 3], reference_year: i32) -> Option<NaiveDate> {
     let mut result:Option<NaiveDate> = None;
     const MIN_YEAR:i32 = 1000;
     const MAX_YEAR:i32 = 2050;
@@ -522,7 +1703,7 @@ fn map_ints_to_dmy(vals: &[i32; 3]) -> Option<NaiveDate> {
         for i in vals.into_iter() {
             match *i {
                 // Relies on fact ints have been parsed into valid magnitudes
-                99 ... MIN_YEAR | MAX_YEAR ... 9999 => {
+                99 ..= MIN_YEAR | MAX_YEAR ..= 9999 => {
                     in_range = false;
                 },
                 _ if *i > 31 => over_31 += 1,
@@ -535,16 +1716,16 @@ fn map_ints_to_dmy(vals: &[i32; 3]) -> Option<NaiveDate> {
             let possible_splits = [(vals[2], (vals[0], vals[1])),
                                    (vals[0], (vals[1], vals[2]))];
 
-            for &(year, dm) in possible_splits.into_iter() {
+            for &(year, dm) in possible_splits.iter() {
                 if MIN_YEAR <= year && year <= MAX_YEAR {
-                    if let Some(date) = map_ints_to_dm(&dm) {
+                    if let Some(date) = map_ints_to_dm(&dm, reference_year) {
                         result = date.with_year(year);
                     }
                 }
             }
             if result.is_none() {
-                for &(year, dm) in possible_splits.into_iter() {
-                    if let Some(date) = map_ints_to_dm(&dm) {
+                for &(year, dm) in possible_splits.iter() {
+                    if let Some(date) = map_ints_to_dm(&dm, reference_year) {
                         result = date.with_year(two_to_four_digit_year(year));
                     }
                 }
@@ -554,8 +1735,7 @@ fn map_ints_to_dmy(vals: &[i32; 3]) -> Option<NaiveDate> {
     result
 }
 
-fn map_ints_to_dm(i:&(i32, i32)) -> Option<NaiveDate> {
-    let year = Local::now().year() as i32;
+fn map_ints_to_dm(i:&(i32, i32), year: i32) -> Option<NaiveDate> {
     // TODO Change to (1..32).contains() etc. when stable
     if 1 <= i.0 && i.0 <= 31 && 1 <= i.1 && i.1 <= 12 {
         NaiveDate::from_ymd_opt(year, i.1 as u32, i.0 as u32)
@@ -576,44 +1756,66 @@ fn two_to_four_digit_year(year: i32) -> i32 {
     }
 }
 
+/// Ways a date written without separators is split into its numbers, for
+/// each length from 4 to 8 digits
+fn date_splits(length: usize) -> &'static [(usize, usize)] {
+    match length {
+        4 => &[(1, 2), (2, 3)],
+        5 => &[(1, 3), (2, 3)],
+        6 => &[(1, 2), (2, 4), (4, 5)],
+        7 => &[(1, 3), (2, 3), (4, 5), (4, 6)],
+        8 => &[(2, 4), (4, 6)],
+        _ => &[],
+    }
+}
+
 pub fn date_match(password: &str) -> Vec<BaseMatch> {
+    date_match_with(password, DEFAULT_CLOCK.current_year())
+}
+
+/// Splits a date written with separators such as "1/2/1990" into its numbers
+/// and the first separator, the same as the regex
+/// ^(\d{1,4})([\s/\\_.-])(\d{1,2})([\s/\\_.-])(\d{1,4})$
+fn split_date_with_separators(token: &str) -> Option<([i32; 3], char)> {
+    let is_separator = |c: char| c.is_whitespace() || "/\\_.-".contains(c);
+    let separator = token.chars().find(|c| is_separator(*c))?;
+    let parts = token.split(is_separator).collect::<Vec<&str>>();
+    if parts.len() != 3 || parts.iter().any(|p| p.is_empty() || !p.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    if parts[0].len() > 4 || parts[1].len() > 2 || parts[2].len() > 4 {
+        return None;
+    }
+    // At most 4 digits so these always fit
+    let dmy = [parts[0].parse().ok()?, parts[1].parse().ok()?, parts[2].parse().ok()?];
+    Some((dmy, separator))
+}
+
+/// Date matching with candidate dates picked by their distance to the given
+/// year
+pub(crate) fn date_match_with(password: &str, reference_year: i32) -> Vec<BaseMatch> {
     let mut result: Vec<BaseMatch> = Vec::new(); 
     let password_len = password.chars().count();
 
-    let date_splits:HashMap<usize, Vec<(usize, usize)>> = {
-        let mut m = HashMap::new();
-        m.insert(4, vec![(1, 2), (2, 3)]);
-        m.insert(5, vec![(1, 3), (2, 3)]);
-        m.insert(6, vec![(1, 2), (2, 4), (4, 5)]);
-        m.insert(7, vec![(1, 3), (2, 3), (4, 5), (4, 6)]);
-        m.insert(8, vec![(2, 4), (4, 6)]);
-        m
-    };
-
-    let maybe_date_no_sep = Regex::new(r"^\d{4,8}$").unwrap();
-    // Can't access previous captures in matches
-    let maybe_date_with_sep = Regex::new(r"^(\d{1,4})([\s/\\_.-])(\d{1,2})([\s/\\_.-])(\d{1,4})$")
-        .unwrap(); 
-    let ref_year = Local::now().year() as i32;
     for i in 0..(cmp::max(password_len, 3)-3) {
         for j in (i+3)..(i+8) {
             if j >= password_len {
                 break;
             }
-            let token = &password[i..j+1];
+            let token = char_slice(password, i, j + 1);
 
-            if !maybe_date_no_sep.is_match(&token) {
+            if !token.bytes().all(|b| b.is_ascii_digit()) {
                 continue;
             }
             let mut candidates:Vec<NaiveDate> = Vec::new();
-            for &(k, l) in date_splits.get(&token.chars().count()).unwrap().iter() {
+            for &(k, l) in date_splits(token.len()).iter() {
                 let a = token[0..k].parse();
                 let b = token[k..l].parse();
                 let c = token[l..].parse();
                 if a.is_err() || b.is_err() || c.is_err() {
                     break;
                 }
-                if let Some(d) = map_ints_to_dmy(&[a.unwrap(), b.unwrap(),c.unwrap()]) {
+                if let Some(d) = map_ints_to_dmy(&[a.unwrap(), b.unwrap(),c.unwrap()], reference_year) {
                     candidates.push(d);
                 }
             }
@@ -623,7 +1825,7 @@ pub fn date_match(password: &str) -> Vec<BaseMatch> {
             let mut best:usize = 0;
             let mut min_distance = i32::max_value();
             for (index, cand) in candidates.iter().enumerate() {
-                let distance = (cand.year() - ref_year).abs();
+                let distance = (cand.year() - reference_year).abs();
                 if distance < min_distance {
                     best = index;
                     min_distance = distance;
@@ -648,23 +1850,9 @@ pub fn date_match(password: &str) -> Vec<BaseMatch> {
             if j >= password_len {
                 break;
             }
-            let token = &password[i..j+1];
-            if let Some(cap) = maybe_date_with_sep.captures(token) 
-            {
-                // Thanks to regex we know these are strings hence the lack of checks
-                let dmy = &[
-                    cap.get(1).unwrap().as_str().parse().unwrap(), 
-                    cap.get(3).unwrap().as_str().parse().unwrap(), 
-                    cap.get(5).unwrap().as_str().parse().unwrap()
-                ];
-                if let Some(d) = map_ints_to_dmy(dmy) {
-                    let sep= cap.get(2)
-                                .unwrap()
-                                .as_str()
-                                .chars()
-                                .next()
-                                .unwrap();
-
+            let token = char_slice(password, i, j + 1);
+            if let Some((dmy, sep)) = split_date_with_separators(token) {
+                if let Some(d) = map_ints_to_dmy(&dmy, reference_year) {
                     let metadata = MatchData::Date {
                         separator: sep,
                         date: d,
@@ -687,59 +1875,278 @@ pub fn date_match(password: &str) -> Vec<BaseMatch> {
 
 #[test]
 fn date_match_test() {
+    let matches = date_match_with("on 1/2/1990", 2017);
+    let m = matches.iter().find(|m| m.token == "1/2/1990").unwrap();
+    assert_eq!((m.start, m.end), (3, 10));
+    assert_eq!(m.data, MatchData::Date {
+        separator: '/',
+        date: NaiveDate::from_ymd_opt(1990, 2, 1).unwrap(),
+    });
+    assert!(date_match_with("1/2//1990", 2017).iter().all(|m| !m.token.contains('/')));
+}
+
+/// zxcvbn 4.4's reading of three numbers as a year, month and day
+fn zxcvbn_map_ints_to_dmy(ints: [i32; 3]) -> Option<(i32, u32, u32)> {
+    const MIN_YEAR: i32 = 1000;
+    const MAX_YEAR: i32 = 2050;
+    if ints[1] > 31 || ints[1] <= 0 {
+        return None;
+    }
+    let (mut over_12, mut over_31, mut under_1) = (0, 0, 0);
+    for &i in ints.iter() {
+        if (99 < i && i < MIN_YEAR) || i > MAX_YEAR {
+            return None;
+        }
+        over_31 += (i > 31) as usize;
+        over_12 += (i > 12) as usize;
+        under_1 += (i <= 0) as usize;
+    }
+    if over_31 >= 2 || over_12 == 3 || under_1 >= 2 {
+        return None;
+    }
+    let map_ints_to_dm = |a: i32, b: i32| {
+        [(a, b), (b, a)].iter()
+                        .cloned()
+                        .find(|&(d, m)| (1..=31).contains(&d) && (1..=12).contains(&m))
+                        .map(|(d, m)| (m as u32, d as u32))
+    };
+    let splits = [(ints[2], ints[0], ints[1]), (ints[0], ints[1], ints[2])];
+    // A four digit year must have a day and month beside it
+    if let Some(&(year, a, b)) = splits.iter().find(|s| MIN_YEAR <= s.0 && s.0 <= MAX_YEAR) {
+        return map_ints_to_dm(a, b).map(|(m, d)| (year, m, d));
+    }
+    splits.iter()
+          .filter_map(|&(year, a, b)| {
+              map_ints_to_dm(a, b).map(|(m, d)| (two_to_four_digit_year(year), m, d))
+          })
+          .next()
+}
+
+/// The date, moved back to the last day of the month if the month is shorter
+fn date_in_month(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+    (1..day + 1).rev().filter_map(|d| NaiveDate::from_ymd_opt(year, month, d)).next()
+}
+
+/// Date matching as zxcvbn 4.4 does it. The numbers are checked differently
+/// to `date_match`, both separators must be the same, and dates inside longer
+/// ones are dropped. zxcvbn doesn't check the day is in the month, such dates
+/// are given the month's last day.
+pub(crate) fn zxcvbn_date_match(password: &str, reference_year: i32) -> Vec<BaseMatch> {
+    let chars = password.chars().collect::<Vec<char>>();
+    let length = chars.len();
+    let mut result: Vec<BaseMatch> = Vec::new();
+    let mut push = |start: usize, end: usize, separator: char, (year, month, day)| {
+        if let Some(date) = date_in_month(year, month, day) {
+            result.push(BaseMatch {
+                pattern: String::from("Date"),
+                start,
+                end,
+                token: chars[start..end + 1].iter().collect(),
+                data: MatchData::Date { separator, date },
+            });
+        }
+    };
 
+    for i in 0..length {
+        for j in i + 3..cmp::min(i + 8, length) {
+            let token = chars[i..j + 1].iter().collect::<String>();
+            if !token.bytes().all(|b| b.is_ascii_digit()) {
+                continue;
+            }
+            let mut best: Option<(i32, u32, u32)> = None;
+            for &(k, l) in date_splits(token.len()).iter() {
+                // At most 8 digits so these always parse
+                let ints = [token[..k].parse().unwrap_or(0),
+                            token[k..l].parse().unwrap_or(0),
+                            token[l..].parse().unwrap_or(0)];
+                if let Some(dmy) = zxcvbn_map_ints_to_dmy(ints) {
+                    let distance = |d: (i32, u32, u32)| (d.0 - reference_year).abs();
+                    let closer = match best {
+                        None => true,
+                        Some(b) => distance(dmy) < distance(b),
+                    };
+                    if closer {
+                        best = Some(dmy);
+                    }
+                }
+            }
+            if let Some(dmy) = best {
+                push(i, j, '\0', dmy);
+            }
+        }
+    }
+    for i in 0..length {
+        for j in i + 5..cmp::min(i + 10, length) {
+            let token = chars[i..j + 1].iter().collect::<String>();
+            if let Some((ints, separator)) = split_date_with_separators(&token) {
+                if !token.chars().filter(|c| !c.is_ascii_digit()).all(|c| c == separator) {
+                    continue;
+                }
+                if let Some(dmy) = zxcvbn_map_ints_to_dmy(ints) {
+                    push(i, j, separator, dmy);
+                }
+            }
+        }
+    }
+
+    let spans = result.iter().map(|m| (m.start, m.end)).collect::<Vec<(usize, usize)>>();
+    result.retain(|m| {
+        !spans.iter().any(|&(start, end)| {
+            (start, end) != (m.start, m.end) && start <= m.start && end >= m.end
+        })
+    });
+    result.sort();
+    result
+}
+
+#[test]
+fn zxcvbn_date_match_test() {
+    let matches = zxcvbn_date_match("1/1/91", 2017);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].data, MatchData::Date {
+        separator: '/',
+        date: NaiveDate::from_ymd_opt(1991, 1, 1).unwrap(),
+    });
+    // Only the full date is kept, not 015_06_04 or 2015 read as 20/1/2005
+    let matches = zxcvbn_date_match("2015_06_04", 2017);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].token, "2015_06_04");
+    assert!(zxcvbn_date_match("1/2-1990", 2017).iter().all(|m| !m.token.contains('/')));
+    // zxcvbn takes 31 February as a date
+    let matches = zxcvbn_date_match("31021990", 2017);
+    assert_eq!(matches[0].data, MatchData::Date {
+        separator: '\0',
+        date: NaiveDate::from_ymd_opt(1990, 2, 28).unwrap(),
+    });
 }
 
 
 pub fn repeat_match(password: &str) -> Vec<BaseMatch> {
+    let context = MatchContext::new(&DEFAULT_ESTIMATOR);
+    repeat_match_with(password, &DEFAULT_ESTIMATOR, &context)
+}
+
+/// Number of times the base of the given length starting at start is repeated
+/// back to back
+fn repeat_count_at(chars: &[char], start: usize, length: usize) -> usize {
+    let base = &chars[start..start + length];
+    let mut count = 1;
+    while start + (count + 1) * length <= chars.len() &&
+          &chars[start + count * length..start + (count + 1) * length] == base {
+        count += 1;
+    }
+    count
+}
+
+/// Length of the longest common prefix of the characters and each of their
+/// suffixes, the Z-function. The first entry is the length of the characters.
+fn common_prefixes(chars: &[char]) -> Vec<usize> {
+    let n = chars.len();
+    let mut z = vec![0; n];
+    if n > 0 {
+        z[0] = n;
+    }
+    // [left, right) is the rightmost window found to match a prefix
+    let (mut left, mut right) = (0, 0);
+    for i in 1..n {
+        if i < right {
+            z[i] = cmp::min(right - i, z[i - left]);
+        }
+        while i + z[i] < n && chars[z[i]] == chars[i + z[i]] {
+            z[i] += 1;
+        }
+        if i + z[i] > right {
+            left = i;
+            right = i + z[i];
+        }
+    }
+    z
+}
+
+/// Finds the earliest repeat in the characters. Returns the start and the
+/// base length of both the greedy and lazy repeats at that start, equivalent
+/// to the regexes (.+)\1+ and (.+?)\1+. Implemented by hand as the regex
+/// crate doesn't support backreferences. A base of length l repeats at a
+/// start if the text l chars on shares a prefix of at least l with it, so each
+/// start takes linear time and passwords without repeats quadratic time.
+fn find_repeat(chars: &[char]) -> Option<(usize, usize, usize)> {
+    for start in 0..chars.len() {
+        let prefixes = common_prefixes(&chars[start..]);
+        let repeats = |l: &usize| prefixes[*l] >= *l;
+        let max_length = (chars.len() - start) / 2;
+        if let Some(lazy) = (1..max_length + 1).find(&repeats) {
+            let greedy = (lazy..max_length + 1).rev().find(&repeats).unwrap();
+            return Some((start, greedy, lazy));
+        }
+    }
+    None
+}
+
+/// Shortest base which repeated covers all the characters, equivalent to the
+/// regex ^(.+?)\1+$
+fn shortest_base(chars: &[char]) -> usize {
+    let prefixes = common_prefixes(chars);
+    (1..chars.len()).find(|&l| {
+                        chars.chunks_exact(l).remainder().is_empty() &&
+                        prefixes[l] == chars.len() - l
+                    })
+                    .unwrap_or(chars.len())
+}
+
+/// Guesses for a repeat's base token. Bases are analysed with a full match
+/// and scoring pass, so the result is cached for bases seen before while
+/// matching the same password.
+fn base_guesses(base: &str, estimator: &Estimator, context: &MatchContext) -> u64 {
+    if let Some(guesses) = context.repeat_guesses.borrow().get(base) {
+        return *guesses;
+    }
+    let analysis = scoring::most_guessable_match_sequence_in(base.to_string(),
+                                                             &omnimatch_with(base, estimator, context),
+                                                             context.scoring());
+    context.repeat_guesses.borrow_mut().insert(base.to_string(), analysis.guesses);
+    analysis.guesses
+}
+
+fn repeat_match_with(password: &str,
+                     estimator: &Estimator,
+                     context: &MatchContext) -> Vec<BaseMatch> {
     let mut result:Vec<BaseMatch> = Vec::new();
-    let count = password.chars().count();
-    
-    let greedy = FancyRegex::new(r"(.+)\1+").unwrap();
-    let lazy = FancyRegex::new(r"(.+?)\1+").unwrap();
-    let lazy_anchored = FancyRegex::new(r"^(.+?)\1+$").unwrap();
+    let chars = password.chars().collect::<Vec<char>>();
+    let count = chars.len();
 
     let mut last_index = 0;
-    while last_index < count {
-        let gmatch = greedy.captures(&password[last_index..]).unwrap();
-        if let Some(gcap) = gmatch {
-            let (gstart, gend) = gcap.pos(0).unwrap();
-            // if greedy matches lazy will
-            let lcap = lazy.captures(&password[last_index..]).unwrap().unwrap();
-            let (lstart, lend) = lcap.pos(0).unwrap();
-
-            let base: String;
-            let mut start = gstart + last_index;
-            let mut end = gend + last_index;
-
-            if gend - gstart > lend - lstart {
-                let lamatch = lazy_anchored.captures(&password[gstart..gend]).unwrap().unwrap(); 
-                base = lamatch.at(1).unwrap().to_string();
+    // Each base is matched and scored in full, so the deadline is checked
+    // before every repeat
+    while last_index < count && !context.timed_out() {
+        if let Some((offset, greedy, lazy)) = find_repeat(&chars[last_index..]) {
+            let start = last_index + offset;
+            let greedy_end = start + greedy * repeat_count_at(&chars, start, greedy);
+            let lazy_end = start + lazy * repeat_count_at(&chars, start, lazy);
+
+            let (end, base_length) = if greedy_end > lazy_end {
+                (greedy_end, shortest_base(&chars[start..greedy_end]))
             } else {
-                start = lstart + last_index;
-                end = lend + last_index;
-                base = lcap.at(1).unwrap().to_string();
-            }
+                (lazy_end, lazy)
+            };
+            let base = chars[start..start + base_length].iter().collect::<String>();
             
-            let base_analysis = scoring::most_guessable_match_sequence(base.clone(), 
-                                                                       omnimatch(base.as_ref()),
-                                                                       false);
-            let repeat_count = (end - start) / base.chars().count();
+            let repeat_count = (end - start) / base_length;
             let metadata = MatchData::Repeat {
+                base_guesses: base_guesses(&base, estimator, context),
                 base_token: base,
-                base_guesses: base_analysis.guesses,
                 repeat_count: repeat_count
             };
             
             let data = BaseMatch {
                 pattern: String::from("Repeat"),
                 start: start,
-                end: end,
-                token: gcap.at(0).unwrap().to_string(),
+                end: end - 1,
+                token: chars[start..end].iter().collect(),
                 data: metadata
             };
             result.push(data);
-            last_index += 1;     
+            // Repeats starting inside this one are covered by it
+            last_index = end;
         } else {
             break;
         }
@@ -752,12 +2159,12 @@ pub fn repeat_match(password: &str) -> Vec<BaseMatch> {
 fn repeat_match_test() {
     let test = "aabaabaabaab";
     let result = repeat_match(test);
-    assert_eq!(result.len(), 10);
+    assert_eq!(result.len(), 1);
     
     let first = result.iter().nth(0).unwrap();
     assert_eq!(first.pattern, "Repeat");
     assert_eq!(first.start, 0);
-    assert_eq!(first.end, test.chars().count());
+    assert_eq!(first.end, test.chars().count() - 1);
     assert_eq!(first.token, test);
     match first.data {
         MatchData::Repeat{ref base_token, ref repeat_count, ..} => {
@@ -769,97 +2176,280 @@ fn repeat_match_test() {
     //let result = repeat_match("abcdefghijklmnopqrstuvwxyz");
 }
 
+#[test]
+fn find_repeat_test() {
+    let chars = |s: &str| s.chars().collect::<Vec<char>>();
+    assert_eq!(find_repeat(&chars("abc")), None);
+    // Greedy and lazy bases differ when the base is itself a repeat
+    assert_eq!(find_repeat(&chars("aaaa")), Some((0, 2, 1)));
+    assert_eq!(find_repeat(&chars("xababy")), Some((1, 2, 2)));
+    assert_eq!(shortest_base(&chars("abababab")), 2);
+    assert_eq!(shortest_base(&chars("abaab")), 5);
+
+    // Agrees with trying every start and base length on all strings of a and
+    // b up to 10 long
+    for length in 1..11 {
+        for n in 0..1u32 << length {
+            let text = (0..length).map(|i| if n >> i & 1 == 1 { 'a' } else { 'b' })
+                                  .collect::<Vec<char>>();
+            let expected = (0..text.len()).filter_map(|start| {
+                let lengths = (1..(text.len() - start) / 2 + 1)
+                    .filter(|&l| repeat_count_at(&text, start, l) > 1)
+                    .collect::<Vec<usize>>();
+                lengths.first().map(|&lazy| (start, *lengths.last().unwrap(), lazy))
+            }).next();
+            assert_eq!(find_repeat(&text), expected, "{:?}", text);
+            let base = (1..length + 1).find(|&l| repeat_count_at(&text, 0, l) * l == length);
+            assert_eq!(Some(shortest_base(&text)), base, "{:?}", text);
+        }
+    }
+}
 
-pub fn spatial_match(password: &str) -> Vec<BaseMatch> {
-    let mut result:Vec<BaseMatch> = Vec::new();
+#[test]
+fn non_ascii_repeat_test() {
+    let result = repeat_match("xéééé");
+    let first = &result[0];
+    assert_eq!(first.start, 1);
+    assert_eq!(first.token, "éééé");
+    match first.data {
+        MatchData::Repeat{ref base_token, ref repeat_count, ..} => {
+            assert_eq!(*base_token, "é");
+            assert_eq!(*repeat_count, 4);
+        },
+        _ => panic!("Expected a repeat match"),
+    };
+}
 
-    let graphs = vec![
-        &*QWERTY_US,
-        &*DVORAK,
-        &*STANDARD_NUMPAD,
-        &*MAC_NUMPAD
-    ];
-    let names = vec![
-        "qwerty",
-        "dvorak",
-        "Keypad",
-        "Mac keypad"
-    ];
+#[test]
+fn repeat_match_cache_test() {
+    let context = MatchContext::new(&DEFAULT_ESTIMATOR);
+    let result = repeat_match_with("abcabc!abcabcabc", &DEFAULT_ESTIMATOR, &context);
+    assert_eq!(result.len(), 2);
+    assert_eq!((result[0].start, result[0].end), (0, 5));
+    assert_eq!((result[1].start, result[1].end), (7, 15));
+    assert_eq!(result[0].data, MatchData::Repeat {
+        base_token: String::from("abc"),
+        base_guesses: context.repeat_guesses.borrow()["abc"],
+        repeat_count: 2,
+    });
+    // The base is only analysed once
+    assert_eq!(context.repeat_guesses.borrow().len(), 1);
+}
 
-    for (name, graph) in names.iter().zip(graphs.iter()) {
-        result.append(&mut spatial_helper(password, name, graph));
+#[test]
+fn repeat_match_cache_sequence_test() {
+    // Matches with every base, including the bases nested inside them,
+    // analysed afresh rather than read from the cache
+    fn uncached_matches(password: &str) -> Vec<BaseMatch> {
+        let context = MatchContext::new(&DEFAULT_ESTIMATOR);
+        let matches = omnimatch_with(password, &DEFAULT_ESTIMATOR, &context);
+        matches.into_iter().map(|mut m| {
+            if let MatchData::Repeat { ref base_token, ref mut base_guesses, .. } = m.data {
+                *base_guesses = scoring::most_guessable_match_sequence_in(base_token.clone(),
+                                                                          &uncached_matches(base_token),
+                                                                          context.scoring()).guesses;
+            }
+            m
+        }).collect()
     }
 
+    let password = "aabaabaabaab";
+    let context = MatchContext::new(&DEFAULT_ESTIMATOR);
+    let cached = omnimatch_with(password, &DEFAULT_ESTIMATOR, &context);
+    assert!(context.repeat_guesses.borrow().len() > 1);
+    let with_cache = scoring::most_guessable_match_sequence_in(password.to_string(),
+                                                               &cached,
+                                                               context.scoring());
+    let without_cache = scoring::most_guessable_match_sequence_in(password.to_string(),
+                                                                  &uncached_matches(password),
+                                                                  context.scoring());
+    assert_eq!(with_cache.guesses, without_cache.guesses);
+    assert_eq!(with_cache.sequence, without_cache.sequence);
+}
+
+#[test]
+fn repeat_match_deadline_test() {
+    static CLOCK: ::clock::SteppingClock = ::clock::SteppingClock::new();
+    let password = "aabbccddeeffgghh";
+    assert_eq!(repeat_match(password).len(), 8);
+
+    // Analysing the first base reads the clock past the deadline, so no
+    // other repeat is tried
+    let mut estimator = Estimator::new();
+    estimator.clock = &CLOCK;
+    estimator.time_budget = Some(Duration::from_millis(5));
+    let context = MatchContext::new(&estimator);
+    let result = repeat_match_with(password, &estimator, &context);
+    assert!(context.timed_out());
+    assert_eq!(result.len(), 1);
+}
+
+
+pub fn spatial_match(password: &str) -> Vec<BaseMatch> {
+    spatial_match_with(password, &DEFAULT_KEYBOARDS)
+}
+
+/// Spatial matching against a custom set of keyboards, each paired with the
+/// graph name reported in the match
+pub fn spatial_match_with(password: &str,
+                          keyboards: &[(String, Keyboard)]) -> Vec<BaseMatch> {
+    tolerant_spatial_match(password, keyboards, 0)
+}
+
+/// Spatial matching allowing up to `slips` repeated or skipped keys in each
+/// walk, such as "qwwerty" or "qwrty". A skipped key is one left out between
+/// two keys in the direction the walk is heading. Walks without slips are
+/// matched as well, so the scorer can pick whichever is least guessable.
+pub fn tolerant_spatial_match(password: &str,
+                              keyboards: &[(String, Keyboard)],
+                              slips: usize) -> Vec<BaseMatch> {
+    let passes = spatial_passes(keyboards, slips);
+    let walks = passes.iter()
+                      .map(|&(name, graph, slips)| spatial_helper(password, name, graph, slips))
+                      .collect::<Vec<Vec<BaseMatch>>>();
+    merge_walks(&passes, &walks)
+}
+
+/// The keyboards walked with the slips allowed, each keyboard without slips
+/// and then with them
+fn spatial_passes(keyboards: &[(String, Keyboard)],
+                  slips: usize) -> Vec<(&str, &Keyboard, usize)> {
+    keyboards.iter()
+             .flat_map(|(name, graph)| {
+                 let tolerant = if slips > 0 { Some((name.as_str(), graph, slips)) } else { None };
+                 Some((name.as_str(), graph, 0)).into_iter().chain(tolerant)
+             })
+             .collect()
+}
+
+/// Combines the walks found on each pass, leaving out walks found with slips
+/// that were found without them
+fn merge_walks(passes: &[(&str, &Keyboard, usize)], walks: &[Vec<BaseMatch>]) -> Vec<BaseMatch> {
+    let mut result:Vec<BaseMatch> = Vec::new();
+    for (&(_, _, slips), found) in passes.iter().zip(walks.iter()) {
+        for m in found.iter() {
+            if slips == 0 || !result.iter().any(|x| x == m && x.data == m.data) {
+                result.push(m.clone());
+            }
+        }
+    }
     result.sort();
     result
 }
 
+/// Progress along a keyboard walk
+#[derive(Clone, Copy)]
+struct Walk {
+    /// Index of the character after the walk
+    end: usize,
+    turns: usize,
+    shift_count: usize,
+    slips: usize,
+}
 
 fn spatial_helper(password: &str, 
                   graph_name: &str, 
-                  graph: &Keyboard) -> Vec<BaseMatch> {
+                  graph: &Keyboard,
+                  max_slips: usize) -> Vec<BaseMatch> {
     let mut result:Vec<BaseMatch> = Vec::new();
-    let password_len = password.chars().count();
+    let average_degree = keyboard::average_degree(graph);
+    let chars = password.chars().collect::<Vec<char>>();
     let mut i = 0;
-    while i < password_len {
-        let mut j = i + 1;
-        let mut turns = 0;
-        let mut previous_direction: Option<&Edge> = None;
-        let current_char = password.chars().nth(i).unwrap();
-        let current_key = graph.find_key(current_char);
-        if current_key.is_none() {
-            i = i +1;
-            continue;
-        }
-        let current_key = current_key.unwrap();
-        let mut previous_key = current_key;
-        let mut shift_count = current_key.is_shifted(current_char) as usize;
-        
-        loop {
-            let mut found = false;
-            if j < password_len {
-                let current_char = password.chars().nth(j).unwrap();
-                let current_key = graph.find_key(current_char);
-                if current_key.is_some() {
-                    let current_key = current_key.unwrap();
-                    if let Some(dir) = graph.edge_weight(previous_key, current_key) {
-                        found = true;    
-                        shift_count += current_key.is_shifted(current_char) as usize;
-                        if Some(dir) != previous_direction {
-                            turns += 1;
-                            previous_direction = Some(dir);
-                        }
+    while i < chars.len() {
+        let first_key = match graph.find_key(chars[i]) {
+            Some(key) => key,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+        let mut current = Walk {
+            end: i + 1,
+            turns: 0,
+            shift_count: first_key.is_shifted(chars[i]) as usize,
+            slips: 0,
+        };
+        // The longest walk found that doesn't end in a slip
+        let mut walk = current;
+        let mut previous_key = first_key;
+        let mut previous_direction: Option<Edge> = None;
+
+        while current.end < chars.len() {
+            let c = chars[current.end];
+            let key = match graph.find_key(c) {
+                Some(key) => key,
+                None => break,
+            };
+            let (direction, slipped) = match graph.edge_weight(previous_key, key) {
+                Some(&direction) => (Some(direction), false),
+                None if current.slips < max_slips => {
+                    match slip_direction(graph, previous_key, key, previous_direction) {
+                        Some(direction) => (direction, true),
+                        None => break,
                     }
-                    previous_key = current_key;
                 }
+                None => break,
+            };
+            if direction != previous_direction {
+                current.turns += 1;
+                previous_direction = direction;
             }
-            if found {
-                j += 1;
+            current.shift_count += key.is_shifted(c) as usize;
+            current.end += 1;
+            if slipped {
+                current.slips += 1;
             } else {
-                if j - i > 2 {
-                    let data = MatchData::Spatial {
-                        graph: graph_name.to_string(),
-                        turns: turns,
-                        shifted_count: shift_count,
-                    };
-
-                    let mat = BaseMatch {
-                        pattern: String::from("Spatial"),
-                        start: i,
-                        end: j - 1,
-                        token: password[i..j].to_string(),
-                        data: data,
-                    };
-                    result.push(mat);
-                }
-                i = j;
-                break;
+                walk = current;
             }
+            previous_key = key;
         }
+
+        if walk.end - i - walk.slips > 2 {
+            let data = MatchData::Spatial {
+                graph: graph_name.to_string(),
+                turns: walk.turns,
+                shifted_count: walk.shift_count,
+                slips: walk.slips,
+                starting_positions: graph.node_count(),
+                average_degree,
+            };
+
+            let mat = BaseMatch {
+                pattern: String::from("Spatial"),
+                start: i,
+                end: walk.end - 1,
+                token: chars[i..walk.end].iter().collect(),
+                data,
+            };
+            result.push(mat);
+        }
+        i = walk.end;
     }
     result
 }
 
+/// Direction a walk continues in when `key` follows `previous` without being
+/// its neighbour: the same direction if the key is repeated, or the direction
+/// of the skipped key if `key` is two steps from `previous` in a straight line.
+/// None if the keys aren't one slip apart.
+fn slip_direction(graph: &Keyboard,
+                  previous: Key,
+                  key: Key,
+                  direction: Option<Edge>) -> Option<Option<Edge>> {
+    if key == previous {
+        return Some(direction);
+    }
+    graph.neighbors(previous)
+         .filter_map(|skipped| {
+             let first = graph.edge_weight(previous, skipped)?;
+             match graph.edge_weight(skipped, key) {
+                 Some(second) if second == first => Some(Some(*first)),
+                 _ => None,
+             }
+         })
+         .next()
+}
+
 
 #[test]
 fn test_spatial_match() {
@@ -868,7 +2458,7 @@ fn test_spatial_match() {
     assert_eq!(matches.len(), 1);
     let mat = matches.iter().nth(0).unwrap();
     match mat.data {
-        MatchData::Spatial{ref graph, ref turns, ref shifted_count} => {
+        MatchData::Spatial{ref graph, ref turns, ref shifted_count, ..} => {
             assert_eq!(*graph, "qwerty");
             assert_eq!(*turns, 2);
             assert_eq!(*shifted_count, 5);
@@ -876,3 +2466,53 @@ fn test_spatial_match() {
         _ => assert!(false),
     }
 }
+
+#[test]
+fn tolerant_spatial_match_test() {
+    let slips = |m: &BaseMatch| match m.data {
+        MatchData::Spatial { slips, .. } => slips,
+        _ => panic!("not a spatial match"),
+    };
+    // A repeated and a skipped key
+    for &password in ["qwwerty", "qwrty"].iter() {
+        assert!(spatial_match(password).iter().all(|m| m.token != password));
+        let m = tolerant_spatial_match(password, &DEFAULT_KEYBOARDS, 1);
+        let walk = m.iter().find(|m| m.token == password).unwrap();
+        assert_eq!(slips(walk), 1);
+        if let MatchData::Spatial { turns, .. } = walk.data {
+            assert_eq!(turns, 1);
+        }
+    }
+
+    // Walks with more slips than allowed stop short
+    let m = tolerant_spatial_match("qwwertyy", &DEFAULT_KEYBOARDS, 1);
+    assert!(m.iter().any(|m| m.token == "qwwerty" && slips(m) == 1));
+    assert!(m.iter().all(|m| m.token != "qwwertyy"));
+    let m = tolerant_spatial_match("qwwerrty", &DEFAULT_KEYBOARDS, 1);
+    assert!(m.iter().all(|m| m.token != "qwwerrty"));
+    let m = tolerant_spatial_match("qwwerrty", &DEFAULT_KEYBOARDS, 2);
+    assert!(m.iter().any(|m| m.token == "qwwerrty" && slips(m) == 2));
+
+    // Keys more than one slip apart end the walk
+    assert!(tolerant_spatial_match("qwey", &DEFAULT_KEYBOARDS, 1).iter()
+                                                                 .all(|m| m.token != "qwey"));
+}
+
+#[test]
+fn spatial_slips_test() {
+    let mut estimator = Estimator::new();
+    let strict = estimator.estimate("zxcvvbnm");
+    estimator.spatial_slips = 1;
+    estimator.explain = true;
+    let tolerant = estimator.estimate("zxcvvbnm");
+    assert!(tolerant.guesses < strict.guesses);
+    assert_eq!(tolerant.sequence.len(), 1);
+    let explanation = tolerant.explanation.unwrap();
+    let slips = explanation.matches[0].factors.iter().find(|f| f.name == "slip variations");
+    // 7 steps that could have slipped, each repeating or skipping a key
+    assert_eq!(slips.unwrap().value, 14);
+
+    // Still no slips when matching the way zxcvbn does
+    estimator.compat = true;
+    assert!(estimator.estimate("zxcvvbnm").sequence.len() > 1);
+}
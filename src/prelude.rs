@@ -0,0 +1,30 @@
+// Items from std used throughout the crate. Without the `std` feature they
+// come from `alloc`, `hashbrown` and `libm` instead.
+
+pub use alloc::string::{String, ToString};
+pub use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+pub use std::collections::HashMap;
+#[cfg(not(feature = "std"))]
+pub use hashbrown::HashMap;
+
+#[cfg(feature = "std")]
+pub fn log10(x: f64) -> f64 {
+    x.log10()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn log10(x: f64) -> f64 {
+    libm::log10(x)
+}
+
+#[cfg(feature = "std")]
+pub fn round(x: f64) -> f64 {
+    x.round()
+}
+
+#[cfg(not(feature = "std"))]
+pub fn round(x: f64) -> f64 {
+    libm::round(x)
+}
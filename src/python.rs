@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use estimator::{Estimator, DEFAULT_ESTIMATOR};
+use matching::{BaseMatch, MatchData};
+use result::PasswordResult;
+
+/// Python wrapper for `Estimator`
+#[pyclass(name = "Estimator", module = "zxcvbn_rs")]
+pub struct PyEstimator {
+    inner: Estimator,
+}
+
+#[pymethods]
+impl PyEstimator {
+    /// Builds an estimator, `time_budget` is in seconds and `threads` is the
+    /// number used by `estimate_batch`. An infinite time budget never runs
+    /// out, NaN or a finite budget too large to represent raises ValueError.
+    #[new]
+    #[pyo3(signature = (max_length = None, time_budget = None, l33t_budget = None, threads = None))]
+    fn new(max_length: Option<usize>,
+           time_budget: Option<f64>,
+           l33t_budget: Option<usize>,
+           threads: Option<usize>) -> PyResult<PyEstimator> {
+        let mut inner = Estimator::new();
+        if let Some(max_length) = max_length {
+            inner.max_length = max_length;
+        }
+        if let Some(l33t_budget) = l33t_budget {
+            inner.l33t_budget = l33t_budget;
+        }
+        if let Some(threads) = threads {
+            inner.threads = threads;
+        }
+        inner.time_budget = match time_budget {
+            Some(s) if s == f64::INFINITY => None,
+            // Negative budgets have already run out, NaN isn't below zero so
+            // it's rejected below
+            Some(s) => Some(Duration::try_from_secs_f64(if s < 0.0 { 0.0 } else { s })
+                .map_err(|e| PyValueError::new_err(format!("Invalid time_budget {}: {}", s, e)))?),
+            None => None,
+        };
+        Ok(PyEstimator { inner })
+    }
+
+    #[pyo3(signature = (password, user_inputs = None))]
+    fn estimate(&self,
+                py: Python,
+                password: &str,
+                user_inputs: Option<Vec<String>>) -> PyPasswordResult {
+        let inputs = user_inputs.unwrap_or_default();
+        py.allow_threads(|| estimate_with(&self.inner, password, &inputs)).into()
+    }
+
+    /// Estimates every password in parallel with the GIL released, so other
+    /// Python threads keep running while a large batch is scored
+    #[pyo3(signature = (passwords, user_inputs = None))]
+    fn estimate_batch(&self,
+                      py: Python,
+                      passwords: Vec<String>,
+                      user_inputs: Option<Vec<String>>) -> Vec<PyPasswordResult> {
+        let inputs = user_inputs.unwrap_or_default();
+        let inputs = inputs.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+        let results = py.allow_threads(|| {
+            self.inner
+                .estimate_many_with_inputs(&passwords, &inputs)
+                .collect::<Vec<PasswordResult>>()
+        });
+        results.into_iter().map(PyPasswordResult::from).collect()
+    }
+}
+
+fn estimate_with(estimator: &Estimator, password: &str, user_inputs: &[String]) -> PasswordResult {
+    let inputs = user_inputs.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+    estimator.estimate_with_inputs(password, &inputs)
+}
+
+/// Python view of a `PasswordResult`
+#[pyclass(name = "PasswordResult", module = "zxcvbn_rs", get_all)]
+pub struct PyPasswordResult {
+    password: String,
+    guesses: u64,
+    guesses_log10: f64,
+    /// Score from 0 to 4
+    score: u8,
+    warning: String,
+    suggestions: Vec<String>,
+    sequence: Vec<PyMatch>,
+    crack_times_seconds: HashMap<String, f64>,
+    /// Milliseconds taken to estimate
+    calculation_time: u32,
+    truncated: bool,
+    l33t_budget_exhausted: bool,
+}
+
+#[pymethods]
+impl PyPasswordResult {
+    fn __repr__(&self) -> String {
+        format!("PasswordResult(score={}, guesses={})", self.score, self.guesses)
+    }
+}
+
+impl From<PasswordResult> for PyPasswordResult {
+    fn from(result: PasswordResult) -> PyPasswordResult {
+        let (warning, suggestions) = match result.feedback {
+            Some(f) => (f.advice, f.suggestions),
+            None => (String::new(), Vec::new()),
+        };
+        let times = &result.crack_times;
+        let crack_times_seconds = [("online_throttling_100_per_hour", times.online_throttling),
+                                   ("online_no_throttling_10_per_second", times.online_no_throttling),
+                                   ("offline_slow_hashing_1e4_per_second", times.offline_slow_hashing),
+                                   ("offline_fast_hashing_1e10_per_second", times.offline_fast_hashing)]
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v))
+            .collect();
+        PyPasswordResult {
+            password: result.password,
+            guesses: result.guesses,
+            guesses_log10: result.guesses_log10,
+            score: result.score.map_or(0, |s| s as u8),
+            warning,
+            suggestions,
+            sequence: result.sequence.into_iter().map(|m| PyMatch { inner: m }).collect(),
+            crack_times_seconds,
+            calculation_time: result.calculation_time,
+            truncated: result.truncated,
+            l33t_budget_exhausted: result.l33t_budget_exhausted,
+        }
+    }
+}
+
+/// Python view of a `BaseMatch` in the sequence of a result
+#[pyclass(name = "Match", module = "zxcvbn_rs")]
+#[derive(Clone)]
+pub struct PyMatch {
+    inner: BaseMatch,
+}
+
+#[pymethods]
+impl PyMatch {
+    #[getter]
+    fn pattern(&self) -> &str {
+        &self.inner.pattern
+    }
+
+    #[getter]
+    fn token(&self) -> &str {
+        &self.inner.token
+    }
+
+    /// Index of the first character of the match
+    #[getter]
+    fn start(&self) -> usize {
+        self.inner.start
+    }
+
+    /// Index of the last character of the match
+    #[getter]
+    fn end(&self) -> usize {
+        self.inner.end
+    }
+
+    /// Pattern specific details of the match
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new_bound(py);
+        match self.inner.data {
+            MatchData::Plain => {}
+            MatchData::Dictionary { ref matched_word, rank, ref dictionary_name, reversed, ref l33t } => {
+                dict.set_item("matched_word", matched_word)?;
+                dict.set_item("rank", rank)?;
+                dict.set_item("dictionary_name", dictionary_name)?;
+                dict.set_item("reversed", reversed)?;
+                dict.set_item("l33t", l33t.is_some())?;
+                if let Some(ref l33t) = *l33t {
+                    let subs = l33t.spans
+                                   .iter()
+                                   .map(|s| (s.token.clone(), s.letter.to_string()))
+                                   .collect::<Vec<(String, String)>>();
+                    dict.set_item("sub", subs)?;
+                }
+            }
+            MatchData::Spatial { ref graph, turns, shifted_count, slips, .. } => {
+                dict.set_item("graph", graph)?;
+                dict.set_item("turns", turns)?;
+                dict.set_item("shifted_count", shifted_count)?;
+                dict.set_item("slips", slips)?;
+            }
+            MatchData::Repeat { ref base_token, base_guesses, repeat_count } => {
+                dict.set_item("base_token", base_token)?;
+                dict.set_item("base_guesses", base_guesses)?;
+                dict.set_item("repeat_count", repeat_count)?;
+            }
+            MatchData::Sequence { ref name, space, ascending } => {
+                dict.set_item("sequence_name", name)?;
+                dict.set_item("sequence_space", space)?;
+                dict.set_item("ascending", ascending)?;
+            }
+            MatchData::Regex { ref name } => {
+                dict.set_item("regex_name", name)?;
+            }
+            MatchData::Date { separator, date } => {
+                dict.set_item("separator", if separator == '\0' {
+                    String::new()
+                } else {
+                    separator.to_string()
+                })?;
+                dict.set_item("date", date.to_string())?;
+            }
+            MatchData::Breach { count } => {
+                dict.set_item("count", count)?;
+            }
+        }
+        Ok(dict)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Match(pattern={:?}, token={:?}, start={}, end={})",
+                self.inner.pattern, self.inner.token, self.inner.start, self.inner.end)
+    }
+}
+
+/// Estimates a password with the default estimator
+#[pyfunction]
+#[pyo3(signature = (password, user_inputs = None))]
+fn estimate(py: Python, password: &str, user_inputs: Option<Vec<String>>) -> PyPasswordResult {
+    let inputs = user_inputs.unwrap_or_default();
+    py.allow_threads(|| estimate_with(&DEFAULT_ESTIMATOR, password, &inputs)).into()
+}
+
+#[pymodule]
+fn zxcvbn_rs(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyEstimator>()?;
+    m.add_class::<PyPasswordResult>()?;
+    m.add_class::<PyMatch>()?;
+    m.add_function(wrap_pyfunction!(self::estimate, m)?)?;
+    Ok(())
+}
+
+
+#[test]
+fn python_estimate_test() {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let module = PyModule::new_bound(py, "zxcvbn_rs").unwrap();
+        zxcvbn_rs(&module).unwrap();
+        let result = module.getattr("estimate")
+                           .unwrap()
+                           .call1(("password",))
+                           .unwrap();
+        assert_eq!(result.getattr("score").unwrap().extract::<u8>().unwrap(), 0);
+        let sequence = result.getattr("sequence").unwrap();
+        let first = sequence.get_item(0).unwrap();
+        assert_eq!(first.getattr("token").unwrap().extract::<String>().unwrap(), "password");
+        let data = first.getattr("data").unwrap();
+        assert_eq!(data.get_item("rank").unwrap().extract::<usize>().unwrap(), 2);
+
+        let estimator = module.getattr("Estimator").unwrap().call0().unwrap();
+        let passwords = vec!["password", "jsmith1987", "correcthorsebatterystaple"];
+        let results = estimator.call_method1("estimate_batch", (passwords, vec!["jsmith"]))
+                               .unwrap()
+                               .extract::<Vec<Bound<PyAny>>>()
+                               .unwrap();
+        assert_eq!(results.len(), 3);
+        let guesses = results[1].getattr("guesses").unwrap().extract::<u64>().unwrap();
+        assert_eq!(guesses,
+                   Estimator::new().estimate_with_inputs("jsmith1987", &["jsmith"]).guesses);
+
+        let class = module.getattr("Estimator").unwrap();
+        let budget = |s: f64| {
+            let kwargs = PyDict::new_bound(py);
+            kwargs.set_item("time_budget", s).unwrap();
+            class.call((), Some(&kwargs))
+        };
+        assert!(budget(f64::INFINITY).is_ok());
+        assert!(budget(-1.0).is_ok());
+        for &s in [f64::NAN, 1e30].iter() {
+            assert!(budget(s).unwrap_err().is_instance_of::<PyValueError>(py));
+        }
+    });
+}
@@ -1,5 +1,7 @@
 use matching::{BaseMatch, MatchData};
-use std::fmt;
+use scoring::{Alternative, Explanation};
+use core::fmt;
+use prelude::*;
 
 /// Provides estimations of the time to crack a password given the number of
 /// guesses required to crack it
@@ -7,16 +9,16 @@ use std::fmt;
 pub struct CrackTimes {
     /// Online attack on a service with rate limiting 
     /// (100 per hour)
-    online_throttling: f64,
+    pub(crate) online_throttling: f64,
     /// Offline attack on a service lacking or with compromised rate limiting 
     /// (10 per second)
-    online_no_throttling: f64,
+    pub(crate) online_no_throttling: f64,
     /// Offline attack, assumes multiple attackers with a slow hash function
     /// (1e4 per second)
-    offline_slow_hashing: f64,
+    pub(crate) offline_slow_hashing: f64,
     /// Offline attack with fast hash and multiple machines
     /// (1e10 per second)
-    offline_fast_hashing: f64,
+    pub(crate) offline_fast_hashing: f64,
 }
 
 impl CrackTimes {
@@ -47,15 +49,15 @@ fn seconds_to_string(seconds: f64) -> String {
     } else if seconds < minute {
         format!("{}s", seconds)
     } else if seconds < hour {
-        format!("{} minute(s)", (seconds/minute).round())
+        format!("{} minute(s)", round(seconds/minute))
     } else if seconds < day {
-        format!("{} hour(s)", (seconds/hour).round())
+        format!("{} hour(s)", round(seconds/hour))
     } else if seconds < month {
-        format!("{} day(s)", (seconds/day).round())
+        format!("{} day(s)", round(seconds/day))
     } else if seconds < year {
-        format!("{} month(s)", (seconds/month).round())
+        format!("{} month(s)", round(seconds/month))
     } else if seconds < century {
-        format!("{} year(s)", (seconds/year).round())
+        format!("{} year(s)", round(seconds/year))
     } else {
         String::from("centuries")
     }
@@ -70,7 +72,7 @@ impl fmt::Display for CrackTimes {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum PasswordScore {
     VeryWeak = 0,
     Weak = 1,
@@ -130,8 +132,21 @@ pub struct PasswordResult {
     pub feedback: Option<Feedback>,
     /// Sequence of words in dictionary that results are based off
     pub sequence: Vec<BaseMatch>,
-    /// Time for zxcvbn to calculate these results
+    /// Time for zxcvbn to calculate these results in milliseconds
     pub calculation_time: u32,
+    /// True if the estimator ran out of time before trying every matcher. The
+    /// guesses are then capped so the score is at most `Weak`
+    pub truncated: bool,
+    /// True if the l33t matcher stopped early after checking the maximum
+    /// number of substitutions. Some l33t matches may have been missed
+    pub l33t_budget_exhausted: bool,
+    /// Breakdown of how the guesses were computed, only recorded when
+    /// `Estimator::explain` is set
+    pub explanation: Option<Explanation>,
+    /// The least guessable match sequences in order of guesses, the first
+    /// normally being `sequence`. Only found when `Estimator::alternatives`
+    /// is set
+    pub alternatives: Vec<Alternative>,
 }
 
 impl fmt::Display for PasswordResult {
@@ -154,6 +169,16 @@ impl fmt::Display for PasswordResult {
                 writeln!(f, "{}", suggestion)?;
             }
         }
+        if let Some(ref explanation) = self.explanation {
+            writeln!(f, "{}", explanation)?;
+        }
+        for alternative in self.alternatives.iter() {
+            let tokens = alternative.sequence
+                                    .iter()
+                                    .map(|m| m.token.as_str())
+                                    .collect::<Vec<&str>>();
+            writeln!(f, "Alternative:\t\t{} ({} guesses)", tokens.join("|"), alternative.guesses)?;
+        }
         writeln!(f, "===============================================")?;
         writeln!(f, "{:#?}", self.sequence)
     }
@@ -172,11 +197,11 @@ impl PasswordResult {
                     suggestions: Vec::new(), 
                     ..Default::default()
                 });
-            } else {
+            } else if !self.sequence.is_empty() {
 
                 let longest_sequence = self.sequence.iter()
-                                                    .max_by(|x, y| x.token.len()
-                                                                          .cmp(&y.token.len()))
+                                                    .max_by(|x, y| x.token.chars().count()
+                                                                          .cmp(&y.token.chars().count()))
                                                     .unwrap();
                 
                 self.feedback = Some(self.get_match_feedback(longest_sequence, 
@@ -228,6 +253,11 @@ impl PasswordResult {
                     advice: String::from("Dates are often easy to guess"),
                     suggestions: vec![String::from("Avoid dates and years associated with you")]
                 },
+            MatchData::Breach{..} => 
+                Feedback {
+                    advice: String::from("This password has appeared in a data breach"),
+                    suggestions: vec![String::from("Use a password you haven't used anywhere else")]
+                },
             _ => Default::default(),
         }
     }
@@ -253,6 +283,8 @@ impl PasswordResult {
                 } else {
                     ""
                 }
+            } else if dictionary_name == "Blocklist" {
+                "This password is on a list of banned passwords"
             } else if dictionary_name == &"Wikipedia" {
                 if only_match {
                     "A word by itself is easy to guess"
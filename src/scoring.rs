@@ -1,16 +1,33 @@
 use result::PasswordResult;
-use matching::{BaseMatch, MatchData};
-use std::collections::HashMap;
-use std::cmp;
-use regex::Regex;
-use chrono::{Local, Datelike};
-use keygraph_rs::*;
+use matching::{self, BaseMatch, MatchData};
+use core::cmp;
+use core::fmt;
+use chrono::Datelike;
+use clock::DEFAULT_CLOCK;
+use prelude::*;
 
 const BRUTEFORCE_CARDINALITY: u64 = 10;
 const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: u64 = 10000;
 const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: u64 = 10;
 const MIN_SUBMATCH_GUESSES_MULTI_CHAR: u64 = 50;
 const MIN_YEAR_SPACE: i32 = 20;
+/// Roughly the number of distinct passwords in the Pwned Passwords corpus. A
+/// breached password is priced as if crackers try them most seen first
+const BREACH_GUESSES: u64 = 1_000_000_000;
+
+/// How matches and the sequences of them are scored
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ScoringConfig {
+    /// Adds 10000^(l - 1) to the guesses for a sequence of l matches
+    pub additive: bool,
+    /// Year dates and recent years are compared against
+    pub reference_year: i32,
+    /// Uses zxcvbn 4.4's formulas where they differ, see `Estimator::compat`
+    pub compat: bool,
+    /// Prices dictionary words by `matching::frequency_rank` where they have
+    /// counts
+    pub cumulative_frequency: bool,
+}
 
 #[derive(Debug)]
 struct MatchScores {
@@ -20,28 +37,31 @@ struct MatchScores {
     length: usize,
 }
 
+/// Dynamic programming table of the best match sequences ending at each
+/// position in the password
 #[derive(Default, Debug)]
-struct OptimalMatch {
+pub(crate) struct OptimalMatch {
     scores: HashMap<usize, Vec<MatchScores>>,
-    exclude_additive: bool,
+    config: ScoringConfig,
 }
 
 impl OptimalMatch {
     fn update(&mut self, pass: &str, m: &BaseMatch, l: usize) {
         let k = m.end;
 
-        let mut pi = estimate_guesses(m, pass);
+        let mut pi = estimate_guesses(m, pass, self.config);
         if l > 1 {
             assert!(self.scores.contains_key(&(m.start - 1)));
             if let Some(score_list) = self.scores.get(&(m.start - 1)) {
                 if let Some(s) = score_list.iter().find(|x| x.length == l - 1) {
-                    pi *= s.pi;
+                    pi = pi.saturating_mul(s.pi);
                 }
             }
         }
-        let mut g = factorial(l as u64) * pi;
-        if self.exclude_additive {
-            g += MIN_GUESSES_BEFORE_GROWING_SEQUENCE.pow((l - 1) as u32);
+        let mut g = factorial(l as u64).saturating_mul(pi);
+        if self.config.additive {
+            g = g.saturating_add(saturating_pow(MIN_GUESSES_BEFORE_GROWING_SEQUENCE,
+                                                (l - 1) as u32));
         }
         if self.scores.contains_key(&k) {
             let scores = self.scores.get_mut(&k).unwrap();
@@ -56,12 +76,103 @@ impl OptimalMatch {
             self.scores.insert(k, vec![]);
         }
 
-        self.scores.get_mut(&k).unwrap().push(MatchScores {
+        let score = MatchScores {
             m: m.clone(),
             g: g,
             pi: pi,
             length: l,
-        });
+        };
+        let scores = self.scores.get_mut(&k).unwrap();
+        // Only the best sequence of each length is kept
+        match scores.iter_mut().find(|x| x.length == l) {
+            Some(existing) => *existing = score,
+            None => scores.push(score),
+        }
+    }
+
+    pub fn new(config: ScoringConfig) -> OptimalMatch {
+        OptimalMatch {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Forgets the sequences ending at or after `length`
+    pub fn truncate(&mut self, length: usize) {
+        self.scores.retain(|k, _| *k < length);
+    }
+
+    /// Scores the sequences ending at each position from `from` to the end of
+    /// the password. Sequences ending before `from` must already be scored.
+    pub fn score_from(&mut self, password: &str, matches: &[BaseMatch], from: usize) {
+        let length = password.chars().count();
+        let chars = 0..length;
+        let matches_by_end = chars.map(|x| matches.iter().filter(|y| y.end == x).collect::<Vec<_>>())
+                                  .collect::<Vec<_>>();
+
+        for k in from..length {
+            for m in matches_by_end[k].iter() {
+                if m.start > 0 {
+                    // update
+                    let lengths = self.scores
+                                      .get(&(m.start - 1))
+                                      .iter()
+                                      .flat_map(|x| x.iter())
+                                      .map(|x| x.length)
+                                      .collect::<Vec<usize>>();
+                    for l in lengths.iter() {
+                        self.update(password, m, l + 1);
+                    }
+                } else {
+                    self.update(password, m, 1);
+                }
+            }
+            // Bruteforce update
+            let bm = bruteforce_match(password, 0, k);
+            self.update(password, &bm, 1);
+            for i in 1..k+1 {
+                let bm = bruteforce_match(password, i, k);
+
+                let lengths = self.scores
+                                  .get(&(i - 1))
+                                  .iter()
+                                  .flat_map(|x| x.iter())
+                                  .map(|x| (x.length, x.m.pattern.clone()))
+                                  .collect::<Vec<(usize, String)>>();
+
+                for l in lengths.iter() {
+                    if l.1 == "Bruteforce" {
+                        continue;
+                    }
+                    self.update(password, &bm, l.0 + 1);
+                }
+            }
+        }
+    }
+
+    /// Unwinds the best sequence covering the whole password into a result
+    pub fn result(&self, password: String) -> PasswordResult {
+        let length = password.chars().count();
+        let optimal_seq = self.unwind(length);
+
+        // format result based on length
+        let guesses = if length == 0 {
+            1u64
+        } else {
+            self.scores
+                .get(&(length - 1))
+                .and_then(|s| s.iter().find(|x| x.length == optimal_seq.len()))
+                .map_or(1u64, |x| x.g)
+        };
+        let g_log10 = log10(guesses as f64);
+
+        PasswordResult {
+            password: password,
+            guesses: guesses,
+            guesses_log10: g_log10,
+            sequence: optimal_seq,
+            ..Default::default()
+        }
     }
 
     fn unwind(&self, n: usize) -> Vec<BaseMatch> {
@@ -72,7 +183,7 @@ impl OptimalMatch {
         let mut g = u64::max_value();
         if let Some(scores) = self.scores.get(&(k as usize)) {
             for score in scores.iter() {
-                if score.g < g {
+                if l == 0 || score.g < g {
                     g = score.g;
                     l = score.length;
                 }
@@ -92,11 +203,11 @@ impl OptimalMatch {
     }
 }
 
-fn factorial(n: u64) -> u64 {
+pub(crate) fn factorial(n: u64) -> u64 {
     let result = if n < 2 {
         1
     } else {
-        (2..(n + 1)).fold(1, |acc, x| acc * x)
+        (2..(n + 1)).fold(1u64, |acc, x| acc.saturating_mul(x))
     };
     result
 }
@@ -110,6 +221,12 @@ fn factorial_test() {
     assert!(factorial(10) == 3628800);
 }
 
+/// Integer power that stops at `u64::MAX` instead of overflowing, long
+/// passwords easily have more guesses than fit in a u64
+fn saturating_pow(base: u64, exp: u32) -> u64 {
+    base.checked_pow(exp).unwrap_or(u64::MAX)
+}
+
 #[allow(non_snake_case)]
 fn nCk(mut n: u64, k: u64) -> u64 {
     let result = if k > n {
@@ -117,9 +234,9 @@ fn nCk(mut n: u64, k: u64) -> u64 {
     } else if 0 == k {
         1
     } else {
-        (1..k + 1).fold(1, |acc, d| {
+        (1..k + 1).fold(1u64, |acc, d| {
             n -= 1;
-            (acc * (n + 1)) / d
+            acc.saturating_mul(n + 1) / d
         })
     };
     result
@@ -135,12 +252,12 @@ fn nCk_test() {
 }
 
 
-fn bruteforce_match(password: &String, start: usize, end: usize) -> BaseMatch {
+fn bruteforce_match(password: &str, start: usize, end: usize) -> BaseMatch {
     BaseMatch {
         pattern: String::from("Bruteforce"),
         start: start,
         end: end,
-        token: password[start..end+1].to_string(),
+        token: matching::char_slice(password, start, end + 1).to_string(),
         data: MatchData::Plain,
     }
 }
@@ -149,89 +266,441 @@ pub fn most_guessable_match_sequence(password: String,
                                      matches: Vec<BaseMatch>,
                                      exclude_additive: bool)
                                      -> PasswordResult {
-
-    let pref = password.as_str();
-    let mut optimal = OptimalMatch {
-        exclude_additive: exclude_additive,
-        ..Default::default()
+    let config = ScoringConfig {
+        additive: exclude_additive,
+        reference_year: DEFAULT_CLOCK.current_year(),
+        ..ScoringConfig::default()
     };
-    let chars = 0..password.len();
-    let matches_by_end = chars.map(|x| matches.iter().filter(|y| y.end == x).collect::<Vec<_>>())
-                              .collect::<Vec<_>>();
-
-    for k in 0..password.len() {
-        for m in matches_by_end[k].iter() {
-            if m.start > 0 {
-                // update
-                let lengths = optimal.scores
-                                     .get(&(m.start - 1))
-                                     .iter()
-                                     .flat_map(|x| x.into_iter())
-                                     .map(|x| x.length)
-                                     .collect::<Vec<usize>>();
-                for l in lengths.iter() {
-                    optimal.update(pref, m, l + 1);
-                }
-            } else {
-                optimal.update(pref, m, 1);
+    most_guessable_match_sequence_in(password, &matches, config)
+}
+
+/// Finds the most guessable match sequence scored with the given
+/// configuration
+pub(crate) fn most_guessable_match_sequence_in(password: String,
+                                               matches: &[BaseMatch],
+                                               config: ScoringConfig) -> PasswordResult {
+    let mut optimal = OptimalMatch::new(config);
+    optimal.score_from(&password, matches, 0);
+    optimal.result(password)
+}
+
+#[test]
+fn unwound_guesses_test() {
+    // The guesses reported are those of the sequence returned
+    let password = "123password";
+    let result = most_guessable_match_sequence(password.to_string(),
+                                               matching::omnimatch(password),
+                                               false);
+    let tokens = result.sequence.iter().map(|m| m.token.as_str()).collect::<Vec<&str>>();
+    assert_eq!(tokens, ["123", "password"]);
+    // 2! * 50 * 50 as both matches are given the minimum of 50 guesses
+    assert_eq!(result.guesses, 5000);
+}
+
+#[test]
+fn best_of_length_test() {
+    // A later, better sequence of the same length replaces the earlier one
+    let password = "654321";
+    let result = most_guessable_match_sequence(password.to_string(),
+                                               matching::omnimatch(password),
+                                               false);
+    assert_eq!(result.sequence.len(), 1);
+    match result.sequence[0].data {
+        MatchData::Dictionary { rank, reversed, .. } => {
+            assert_eq!(rank, 1);
+            assert!(reversed);
+        }
+        _ => panic!("Expected a dictionary match"),
+    }
+    assert_eq!(result.guesses, 2);
+}
+
+/// Extends a result scored for the start of the password to cover the rest of
+/// it, with the unanalysed remainder treated as a single bruteforce match
+pub(crate) fn append_bruteforce(result: &mut PasswordResult, password: &str) {
+    let start = result.password.chars().count();
+    let end = password.chars().count();
+    if end <= start {
+        return;
+    }
+    let bm = bruteforce_match(password, start, end - 1);
+    let guesses = bruteforce_guesses(&bm);
+    result.guesses = result.guesses.saturating_mul(guesses);
+    result.guesses_log10 = log10(result.guesses as f64);
+    result.password = password.to_string();
+    for alternative in result.alternatives.iter_mut() {
+        alternative.guesses = alternative.guesses.saturating_mul(guesses);
+        alternative.sequence.push(bm.clone());
+    }
+    if let Some(ref mut explanation) = result.explanation {
+        explanation.unanalysed_guesses = guesses;
+        explanation.matches.push(MatchExplanation {
+            pattern: bm.pattern.clone(),
+            token: bm.token.clone(),
+            factors: bruteforce_factors(&bm),
+            min_guesses: bruteforce_min_guesses(bm.token.chars().count()),
+            guesses,
+        });
+    }
+    result.sequence.push(bm);
+}
+
+#[test]
+fn long_password_test() {
+    use estimator::Estimator;
+
+    let mut estimator = Estimator::new();
+    let result = estimator.estimate("kq8#mz!Qp2@xL9vR4wT7yZ");
+    assert!(result.guesses > 1_000_000_000);
+
+    estimator.max_length = 24;
+
+    let password = "correcthorse".repeat(1000);
+    let result = estimator.estimate(&password);
+    assert_eq!(result.password, password);
+    let last = result.sequence.last().unwrap();
+    assert_eq!(last.pattern, "Bruteforce");
+    assert_eq!((last.start, last.end), (24, password.len() - 1));
+    assert_eq!(result.guesses, u64::MAX);
+    assert!(!result.truncated);
+}
+
+/// How the guesses for a password were worked out, recorded when
+/// `Estimator::explain` is set
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Explanation {
+    /// Breakdown for each match in `PasswordResult::sequence`
+    pub matches: Vec<MatchExplanation>,
+    /// Orders the analysed matches could be guessed in, the factorial of
+    /// their number
+    pub factorial: u64,
+    /// Product of the guesses for the analysed matches
+    pub product: u64,
+    /// Guesses added for the number of matches, 0 unless the sequence was
+    /// scored with the additive term
+    pub additive: u64,
+    /// `factorial * product + additive`, the guesses for the analysed
+    /// characters
+    pub sequence_guesses: u64,
+    /// Guesses for the characters past `Estimator::max_length`, scored as one
+    /// bruteforce match. 1 if the whole password was analysed
+    pub unanalysed_guesses: u64,
+    /// Cap applied to the guesses because the time budget ran out
+    pub truncated_to: Option<u64>,
+}
+
+/// Breakdown of the guesses for a single match
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MatchExplanation {
+    pub pattern: String,
+    pub token: String,
+    /// Terms multiplied together for the match's guesses
+    pub factors: Vec<Factor>,
+    /// Least guesses the match can be given at its length
+    pub min_guesses: u64,
+    /// Product of the factors, raised to `min_guesses` if lower
+    pub guesses: u64,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let analysed = self.matches.len() - if self.unanalysed_guesses > 1 { 1 } else { 0 };
+        for m in self.matches.iter() {
+            write!(f, "{} {:?}:", m.pattern, m.token)?;
+            for (i, factor) in m.factors.iter().enumerate() {
+                let sep = if i == 0 { "" } else { " x" };
+                write!(f, "{} {} {}", sep, factor.name, factor.value)?;
             }
+            writeln!(f, " = {} (min {})", m.guesses, m.min_guesses)?;
         }
-        // Bruteforce update
-        let bm = bruteforce_match(&password, 0, k);
-        optimal.update(pref, &bm, 1);
-        for i in 1..k+1 {
-            let bm = bruteforce_match(&password, i, k);
-
-            let lengths = optimal.scores
-                                 .get(&(i - 1))
-                                 .iter()
-                                 .flat_map(|x| x.into_iter())
-                                 .map(|x| (x.length, x.m.pattern.clone()))
-                                 .collect::<Vec<(usize, String)>>();
-
-            for l in lengths.iter() {
-                if l.1 == "Bruteforce".to_string() {
-                    continue;
+        write!(f, "{}! x {} + {} = {}",
+               analysed, self.product, self.additive, self.sequence_guesses)?;
+        if self.unanalysed_guesses > 1 {
+            write!(f, ", x {} unanalysed", self.unanalysed_guesses)?;
+        }
+        if let Some(cap) = self.truncated_to {
+            write!(f, ", capped at {} after running out of time", cap)?;
+        }
+        Ok(())
+    }
+}
+
+/// Explains the guesses in a result from `OptimalMatch::result` for a
+/// sequence scored with the same configuration
+pub(crate) fn explain(result: &PasswordResult, config: ScoringConfig) -> Explanation {
+    let matches = result.sequence
+                        .iter()
+                        .map(|m| explain_match(m, &result.password, config))
+                        .collect::<Vec<_>>();
+    let l = matches.len();
+    let orderings = factorial(l as u64);
+    let product = matches.iter().fold(1u64, |acc, m| acc.saturating_mul(m.guesses));
+    let additive = if config.additive && l > 0 {
+        saturating_pow(MIN_GUESSES_BEFORE_GROWING_SEQUENCE, (l - 1) as u32)
+    } else {
+        0
+    };
+    Explanation {
+        matches,
+        factorial: orderings,
+        product,
+        additive,
+        sequence_guesses: orderings.saturating_mul(product).saturating_add(additive),
+        unanalysed_guesses: 1,
+        truncated_to: None,
+    }
+}
+
+#[test]
+fn explain_test() {
+    use estimator::Estimator;
+
+    let mut estimator = Estimator::new();
+    assert!(estimator.estimate("Password1990").explanation.is_none());
+
+    estimator.explain = true;
+    let result = estimator.estimate("Password1990");
+    let explanation = result.explanation.unwrap();
+    assert_eq!(explanation.matches.len(), result.sequence.len());
+    assert_eq!(explanation.sequence_guesses, result.guesses);
+    assert_eq!(explanation.factorial, factorial(result.sequence.len() as u64));
+    let product = explanation.matches.iter().fold(1u64, |acc, m| acc.saturating_mul(m.guesses));
+    assert_eq!(explanation.product, product);
+    for m in explanation.matches.iter() {
+        let factors = m.factors.iter().fold(1u64, |acc, f| acc.saturating_mul(f.value));
+        assert_eq!(m.guesses, cmp::max(factors, m.min_guesses));
+    }
+    let dictionary = explanation.matches.iter().find(|m| m.pattern == "Dictionary").unwrap();
+    let names = dictionary.factors.iter().map(|f| f.name).collect::<Vec<_>>();
+    assert_eq!(names, ["rank", "uppercase variations", "l33t variations", "reversed"]);
+
+    estimator.max_length = 8;
+    let result = estimator.estimate("correcthorse");
+    let explanation = result.explanation.unwrap();
+    assert_eq!(explanation.matches.last().unwrap().token, "orse");
+    assert_eq!(explanation.sequence_guesses.saturating_mul(explanation.unanalysed_guesses),
+               result.guesses);
+}
+
+/// A sequence of matches covering the password and the guesses for it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alternative {
+    pub guesses: u64,
+    pub sequence: Vec<BaseMatch>,
+}
+
+/// Entry in the table of the best sequences ending at each position
+#[derive(Clone, Copy, Debug)]
+struct Step {
+    /// Product of the guesses for the matches in the sequence
+    pi: u64,
+    /// The last match, an index into the candidates
+    m: usize,
+    /// Whether the sequence before the match ends in bruteforce, and its
+    /// index in that list
+    prev: (usize, usize),
+}
+
+/// Finds the `k` sequences with the fewest guesses, scored the same way as
+/// `most_guessable_match_sequence`. Sequences differ in how the password is
+/// split or in the pattern matched for some part. Where several matches have
+/// the same pattern and span only the most guessable is used.
+pub(crate) fn alternatives(password: &str,
+                           matches: &[BaseMatch],
+                           config: ScoringConfig,
+                           k: usize) -> Vec<Alternative> {
+    let n = password.chars().count();
+    if k == 0 {
+        return Vec::new();
+    } else if n == 0 {
+        return vec![Alternative { guesses: 1, sequence: Vec::new() }];
+    }
+
+    let mut candidates: Vec<(BaseMatch, u64)> = Vec::new();
+    for m in matches.iter() {
+        let guesses = estimate_guesses(m, password, config);
+        let same = candidates.iter().position(|(c, _)| {
+            c.start == m.start && c.end == m.end && c.pattern == m.pattern
+        });
+        match same {
+            Some(i) if candidates[i].1 > guesses => candidates[i] = (m.clone(), guesses),
+            Some(_) => {}
+            None => candidates.push((m.clone(), guesses)),
+        }
+    }
+    let given = candidates.len();
+
+    // table[end][length - 1][ends in bruteforce] holds the k sequences with
+    // the lowest product, two bruteforce matches are never adjacent
+    let mut table: Vec<Vec<[Vec<Step>; 2]>> = Vec::with_capacity(n);
+    for end in 0..n {
+        for start in 0..end + 1 {
+            let bm = bruteforce_match(password, start, end);
+            let guesses = estimate_guesses(&bm, password, config);
+            candidates.push((bm, guesses));
+        }
+        let mut lists: Vec<[Vec<Step>; 2]> = vec![[Vec::new(), Vec::new()]; end + 1];
+        for (i, &(ref m, guesses)) in candidates.iter().enumerate() {
+            if m.end != end {
+                continue;
+            }
+            let bruteforce = (i >= given) as usize;
+            if m.start == 0 {
+                lists[0][bruteforce].push(Step { pi: guesses, m: i, prev: (0, 0) });
+                continue;
+            }
+            for (l, previous) in table[m.start - 1].iter().enumerate() {
+                for (was_bruteforce, steps) in previous.iter().enumerate() {
+                    if bruteforce == 1 && was_bruteforce == 1 {
+                        continue;
+                    }
+                    for (j, step) in steps.iter().enumerate() {
+                        lists[l + 1][bruteforce].push(Step {
+                            pi: step.pi.saturating_mul(guesses),
+                            m: i,
+                            prev: (was_bruteforce, j),
+                        });
+                    }
                 }
-                optimal.update(pref, &bm, l.0 + 1);
             }
         }
+        for steps in lists.iter_mut().flat_map(|x| x.iter_mut()) {
+            steps.sort_by_key(|x| x.pi);
+            steps.truncate(k);
+        }
+        table.push(lists);
     }
-    let optimal_seq = optimal.unwind(password.len());
-    let optimal_length = optimal_seq.iter().count() - 1;
 
-    // format result based on length
-    let guesses = if password.len() == 0 {
-        1u64
-    } else {
-        let mut gs = 1u64;
-        if let Some(s) = optimal.scores.get(&(password.len() - 1)) {
-            let ms = s.get(optimal_length);
-            if ms.is_some() {
-                gs = ms.unwrap().g;
+    let mut ends = Vec::new();
+    for (l, lists) in table[n - 1].iter().enumerate() {
+        let length = l + 1;
+        let additive = if config.additive {
+            saturating_pow(MIN_GUESSES_BEFORE_GROWING_SEQUENCE, l as u32)
+        } else {
+            0
+        };
+        for (bruteforce, steps) in lists.iter().enumerate() {
+            for (j, step) in steps.iter().enumerate() {
+                let g = factorial(length as u64).saturating_mul(step.pi).saturating_add(additive);
+                ends.push((g, length, bruteforce, j));
+            }
+        }
+    }
+    ends.sort();
+    ends.into_iter()
+        .take(k)
+        .map(|(guesses, length, bruteforce, j)| {
+            let mut sequence = Vec::with_capacity(length);
+            let (mut end, mut l, mut position) = (n - 1, length - 1, (bruteforce, j));
+            loop {
+                let step = table[end][l][position.0][position.1];
+                let m = &candidates[step.m].0;
+                sequence.push(m.clone());
+                if m.start == 0 {
+                    break;
+                }
+                end = m.start - 1;
+                l -= 1;
+                position = step.prev;
             }
-        } 
-        gs
+            sequence.reverse();
+            Alternative { guesses, sequence }
+        })
+        .collect()
+}
+
+#[test]
+fn alternatives_test() {
+    use estimator::Estimator;
+
+    let mut estimator = Estimator::new();
+    assert!(estimator.estimate("password2019").alternatives.is_empty());
+
+    estimator.alternatives = 4;
+    let result = estimator.estimate("password2019");
+    let alternatives = result.alternatives;
+    assert_eq!(alternatives.len(), 4);
+    assert_eq!(alternatives[0].guesses, result.guesses);
+    let parts = |sequence: &[BaseMatch]| {
+        sequence.iter().map(|m| (m.pattern.clone(), m.start, m.end)).collect::<Vec<_>>()
     };
-    let g_log10 = (guesses as f64).log10();
+    assert_eq!(parts(&alternatives[0].sequence), parts(&result.sequence));
+    for (i, alternative) in alternatives.iter().enumerate().skip(1) {
+        assert!(alternatives[i - 1].guesses <= alternative.guesses);
+        assert!(alternatives[..i].iter().all(|a| parts(&a.sequence) != parts(&alternative.sequence)));
+    }
+    for alternative in alternatives.iter() {
+        let tokens = alternative.sequence.iter().map(|m| m.token.as_str()).collect::<String>();
+        assert_eq!(tokens, "password2019");
+    }
+    assert!(alternatives.iter().any(|a| a.sequence.len() == 2 && a.sequence[1].pattern == "Date"));
+
+    estimator.max_length = 8;
+    let result = estimator.estimate("password2019");
+    for alternative in result.alternatives.iter() {
+        assert_eq!(alternative.sequence.last().unwrap().token, "2019");
+    }
+    assert_eq!(result.alternatives[0].guesses, result.guesses);
+}
 
-    PasswordResult {
-        password: password.clone(),
-        guesses: guesses,
-        guesses_log10: g_log10,
-        sequence: optimal_seq,
-        ..Default::default()
+fn explain_match(m: &BaseMatch, password: &str, config: ScoringConfig) -> MatchExplanation {
+    let factors = guess_factors(m, config);
+    let min_guesses = min_guesses(m, password);
+    MatchExplanation {
+        pattern: m.pattern.clone(),
+        token: m.token.clone(),
+        guesses: cmp::max(product(&factors), min_guesses),
+        factors,
+        min_guesses,
     }
 }
 
 
-fn estimate_guesses(m: &BaseMatch, password: &str) -> u64 {
+/// A term multiplied into the guesses for a match
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Factor {
+    pub name: &'static str,
+    pub value: u64,
+}
+
+fn factor(name: &'static str, value: u64) -> Factor {
+    Factor { name, value }
+}
+
+fn product(factors: &[Factor]) -> u64 {
+    factors.iter().fold(1u64, |acc, f| acc.saturating_mul(f.value))
+}
+
+fn estimate_guesses(m: &BaseMatch, password: &str, config: ScoringConfig) -> u64 {
     // Here in coffeescript they dynamically add more struct fields to the
     // match which exist in the result anyway. It just seems so wasteful.
     // gonna think of something better but until then this will suffice.
+    cmp::max(product(&guess_factors(m, config)), min_guesses(m, password))
+}
+
+/// Factors whose product is the guesses for the match before the minimum is
+/// applied
+fn guess_factors(m: &BaseMatch, config: ScoringConfig) -> Vec<Factor> {
+    match m.pattern.as_str() {
+        "Bruteforce" => bruteforce_factors(m),
+        "Dictionary" => dictionary_factors(m, config),
+        "Repeat" => repeat_factors(m),
+        "Sequence" => sequence_factors(m, config.compat),
+        "Regex" => regex_factors(m, config.reference_year),
+        "Date" => date_factors(m, config),
+        "Spatial" if config.compat => zxcvbn_spatial_factors(m),
+        "Spatial" => spatial_factors(m),
+        "Breach" => breach_factors(m),
+        _ => vec![factor("unknown pattern", 0)],
+    }
+}
 
-    let min_guesses = if m.token.len() < password.len() {
-        if m.token.len() == 1 {
+/// Least guesses a match in the password is given. Matches for part of the
+/// password get more so sequences of short matches aren't favoured.
+fn min_guesses(m: &BaseMatch, password: &str) -> u64 {
+    let token_length = m.token.chars().count();
+    let min_guesses = if token_length < password.chars().count() {
+        if token_length == 1 {
             MIN_SUBMATCH_GUESSES_SINGLE_CHAR
         } else {
             MIN_SUBMATCH_GUESSES_MULTI_CHAR
@@ -239,71 +708,134 @@ fn estimate_guesses(m: &BaseMatch, password: &str) -> u64 {
     } else {
         1u64
     };
-    let guesses = match m.pattern.as_str() {
-        "Bruteforce" => bruteforce_guesses(&m),
-        "Dictionary" => dictionary_guesses(&m),
-        "Repeat" => repeat_guesses(&m),
-        "Sequence" => sequence_guesses(&m),
-        "Regex" => regex_guesses(&m),
-        "Date" => date_guesses(&m),
-        "Spatial" => spatial_guesses(&m),
-        _ => 0u64,
-    };
-
-    cmp::max(guesses, min_guesses)
+    if m.pattern == "Bruteforce" {
+        cmp::max(min_guesses, bruteforce_min_guesses(token_length))
+    } else {
+        min_guesses
+    }
 }
 
 
 fn bruteforce_guesses(m: &BaseMatch) -> u64 {
-    let min_guesses = if m.token.len() == 1 {
+    cmp::max(bruteforce_min_guesses(m.token.chars().count()),
+             product(&bruteforce_factors(m)))
+}
+
+fn bruteforce_min_guesses(token_length: usize) -> u64 {
+    if token_length == 1 {
         MIN_SUBMATCH_GUESSES_SINGLE_CHAR + 1u64
     } else {
         MIN_SUBMATCH_GUESSES_MULTI_CHAR + 1u64
-    };
-    cmp::max(min_guesses,
-             BRUTEFORCE_CARDINALITY.pow(m.token.len() as u32))
+    }
+}
+
+fn bruteforce_factors(m: &BaseMatch) -> Vec<Factor> {
+    let token_length = m.token.chars().count();
+    vec![factor("cardinality ^ length",
+                saturating_pow(BRUTEFORCE_CARDINALITY, token_length as u32))]
 }
 
-fn dictionary_guesses(m: &BaseMatch) -> u64 {
+fn dictionary_factors(m: &BaseMatch, config: ScoringConfig) -> Vec<Factor> {
     match m.data {
-        MatchData::Dictionary {rank, reversed, ..} => {
-            let urank = uppercase_variations(m);
-            let l33t_rank = l33t_variations(m);
+        MatchData::Dictionary {ref matched_word, rank, ref dictionary_name, reversed, ..} => {
             let reversed_rank = if reversed {
                 2u64
             } else {
                 1u64
             };
-            (rank as u64) * urank * l33t_rank * reversed_rank
+            let frequency_rank = if config.cumulative_frequency {
+                matching::frequency_rank(dictionary_name, matched_word)
+            } else {
+                None
+            };
+            let rank = match frequency_rank {
+                Some(rank) => factor("frequency rank", rank as u64),
+                None => factor("rank", rank as u64),
+            };
+            vec![rank,
+                 factor("uppercase variations", uppercase_variations(m, config.compat)),
+                 factor("l33t variations", l33t_variations(m, config.compat)),
+                 factor("reversed", reversed_rank)]
         }
-        _ => 0u64,
+        _ => vec![factor("rank", 0)],
     }
 
 }
 
-fn uppercase_variations(m: &BaseMatch) -> u64 {
+#[test]
+fn cumulative_frequency_test() {
+    use estimator::Estimator;
+
+    let mut estimator = Estimator::new();
+    estimator.explain = true;
+    let by_rank = estimator.estimate("staple");
+    estimator.cumulative_frequency = true;
+    let by_frequency = estimator.estimate("staple");
+    let rank = match by_rank.sequence[0].data {
+        MatchData::Dictionary { rank, .. } => rank as u64,
+        _ => panic!("staple should be a dictionary word"),
+    };
+    let explanation = by_frequency.explanation.unwrap();
+    let frequency_rank = &explanation.matches[0].factors[0];
+    assert_eq!(frequency_rank.name, "frequency rank");
+    assert!(frequency_rank.value > rank);
+    assert!(by_frequency.guesses > by_rank.guesses);
+
+    // Names have no counts so keep their rank
+    assert_eq!(estimator.estimate("jennifer").guesses, Estimator::new().estimate("jennifer").guesses);
+    estimator.compat = true;
+    let compat = estimator.estimate("staple");
+    assert_eq!(compat.explanation.unwrap().matches[0].factors[0].name, "rank");
+}
+
+/// Ways the letters of a dictionary word could have been capitalised. zxcvbn
+/// 4.4 only counts the ASCII letters
+fn uppercase_variations(m: &BaseMatch, compat: bool) -> u64 {
     let token = m.token.as_str();
+    let (ucount, lcount) = if compat {
+        (token.chars().filter(char::is_ascii_uppercase).count() as u64,
+         token.chars().filter(char::is_ascii_lowercase).count() as u64)
+    } else {
+        (token.chars().filter(|x| x.is_uppercase()).count() as u64,
+         token.chars().filter(|x| x.is_lowercase()).count() as u64)
+    };
 
-    if token.to_lowercase() == token {
+    if token.to_lowercase() == token || (compat && ucount == 0) {
         return 1u64;
     }
-    let first_upper = Regex::new(r"^[A-Z][^A-Z]+$").unwrap();
-    let last_upper = Regex::new(r"^[^A-Z]+[A-Z]$").unwrap();
-    if token.to_uppercase() == token || first_upper.is_match(token) || last_upper.is_match(token) {
+    if token.to_uppercase() == token || capitalised_at_end(token) || (compat && lcount == 0) {
         return 2u64;
     }
 
-    let ucount = token.chars().filter(|x| x.is_uppercase()).count() as u64;
-    let lcount = token.chars().filter(|x| x.is_lowercase()).count() as u64;
     let mut variations = 0u64;
 
     for i in 1..(cmp::min(ucount, lcount) + 1) {
-        variations += nCk(ucount + lcount, i);
+        variations = variations.saturating_add(nCk(ucount + lcount, i));
     }
     variations
 }
 
-fn l33t_variations(m: &BaseMatch) -> u64 {
+/// True if the first or last character is the token's only capital as in
+/// "Password" or "passworD", matching the regexes ^[A-Z][^A-Z]+$ and
+/// ^[^A-Z]+[A-Z]$
+fn capitalised_at_end(token: &str) -> bool {
+    let chars = token.chars().collect::<Vec<char>>();
+    let capitals = chars.iter().filter(|c| c.is_ascii_uppercase()).count();
+    chars.len() > 1 && capitals == 1 &&
+        (chars[0].is_ascii_uppercase() || chars[chars.len() - 1].is_ascii_uppercase())
+}
+
+#[test]
+fn capitalised_at_end_test() {
+    assert!(capitalised_at_end("Password"));
+    assert!(capitalised_at_end("passworD"));
+    assert!(capitalised_at_end("Pässwörd"));
+    assert!(!capitalised_at_end("PassworD"));
+    assert!(!capitalised_at_end("pAssword"));
+    assert!(!capitalised_at_end("P"));
+}
+
+fn l33t_variations(m: &BaseMatch, compat: bool) -> u64 {
     let mut result = 1u64;
     
     let lower_token = m.token.to_lowercase();
@@ -311,25 +843,27 @@ fn l33t_variations(m: &BaseMatch) -> u64 {
         if let Some(ref data) = *l33t {
             
             for (k, v) in data.l33t_subs.iter() {
-                let subbed = lower_token.chars()
-                                        .filter(|x| x == k)
-                                        .count();
+                let subbed = data.spans.iter()
+                                       .filter(|x| x.token.to_lowercase() == k.to_lowercase())
+                                       .count();
                 
                 let unsubbed = lower_token.chars()
                                           .filter(|x| v.contains(*x))
                                           .count();
 
                 if unsubbed == 0 || subbed == 0 {
-                    result *= 2;
+                    result = result.saturating_mul(2);
                 } else {
                     let mut possibilities:u64 = 0;
                     for i in 1..cmp::min(subbed, unsubbed)+1 {
-                        possibilities += nCk((subbed+unsubbed) as u64, i as u64);
+                        possibilities = possibilities.saturating_add(
+                            nCk((subbed+unsubbed) as u64, i as u64));
                     }
                     // Modifier for mutli-sub matcher I implemented that differs
                     // from dropboxes. Might be doing possibilties for each
                     // combo is more accurate.
-                    result *= v.chars().count() as u64 * possibilities;
+                    let letters = if compat { 1 } else { v.chars().count() as u64 };
+                    result = result.saturating_mul(letters.saturating_mul(possibilities));
                 }
             }
         }
@@ -339,38 +873,45 @@ fn l33t_variations(m: &BaseMatch) -> u64 {
 }
 
 
-fn repeat_guesses(m: &BaseMatch) -> u64 {
+fn repeat_factors(m: &BaseMatch) -> Vec<Factor> {
     if let MatchData::Repeat{ ref base_guesses, ref repeat_count, ..} = m.data {
-        *base_guesses * *repeat_count as u64
+        vec![factor("base guesses", *base_guesses),
+             factor("repeat count", *repeat_count as u64)]
     } else {
-        1u64
+        Vec::new()
     }
 }
 
-fn sequence_guesses(m: &BaseMatch) -> u64 {
+fn sequence_factors(m: &BaseMatch, compat: bool) -> Vec<Factor> {
     assert_eq!(m.pattern, "Sequence");
 
-    let digits = Regex::new(r"\d").unwrap();
-    let extremes = Regex::new(r"[019azAZ]").unwrap();
+    let first = m.token.chars().next();
+    let alphabet = matching::sequence_alphabet(&m.token);
     
-    let mut base_guesses = if m.token.len() == 0 {
-        0u64
-    } else if extremes.is_match(&m.token[0..1]) {
-        4u64
-    } else if digits.is_match(&m.token[0..1]) {
-        10u64
-    } else {
-        26u64
+    let base_guesses = match (first, alphabet) {
+        (None, _) => 0u64,
+        // zxcvbn 4.4 only knows the ends of the Latin alphabets and digits,
+        // every other sequence may start at any of 26 characters
+        (Some(c), _) if compat && "aAzZ019".contains(c) => 4u64,
+        (Some(c), _) if compat && c.is_ascii_digit() => 10u64,
+        (Some(_), _) if compat => 26u64,
+        (Some(c), Some(a)) if a.extremes.contains(c) => 4u64,
+        _ => match m.data {
+            MatchData::Sequence{space, ..} => space as u64,
+            _ => 26u64,
+        },
     };
 
-    match m.data {
-        MatchData::Sequence{ref ascending, ..} if !ascending => base_guesses *= 2u64,
-        _ => {}
-    }
-    base_guesses * m.token.chars().count() as u64
+    let direction = match m.data {
+        MatchData::Sequence{ref ascending, ..} if !ascending => 2u64,
+        _ => 1u64,
+    };
+    vec![factor("starting characters", base_guesses),
+         factor("descending", direction),
+         factor("length", m.token.chars().count() as u64)]
 }
 
-fn regex_guesses(m: &BaseMatch) -> u64 {
+fn regex_factors(m: &BaseMatch, reference_year: i32) -> Vec<Factor> {
     let class_bases:HashMap<String, u64> = {
         let mut m = HashMap::new();
         m.insert(String::from("alpha lower"), 26);
@@ -386,70 +927,128 @@ fn regex_guesses(m: &BaseMatch) -> u64 {
         _ => "",
     };
     
-    let mut guesses = 1u64;
-
     if let Some(base) = class_bases.get(reg_name) {
-        guesses = base.pow(m.token.chars().count() as u32);  
+        vec![factor("class size ^ length",
+                    saturating_pow(*base, m.token.chars().count() as u32))]
     } else if reg_name == "recent year" {
-        let reference_year = Local::now().year() as i32;
         let year:i32 = m.token.parse().expect("Year not a number");
         let year_space = (year - reference_year).abs();
-        guesses = cmp::max(year_space, MIN_YEAR_SPACE) as u64;
+        vec![factor("year space", cmp::max(year_space, MIN_YEAR_SPACE) as u64)]
+    } else {
+        Vec::new()
     }
-
-    guesses
 }
 
-fn date_guesses(m: &BaseMatch) -> u64 {
-    let mut result:u64 = 0;
-    let reference_year = Local::now().year();
-    
+fn date_factors(m: &BaseMatch, config: ScoringConfig) -> Vec<Factor> {
     if let MatchData::Date { separator, date} = m.data {
-        result = 365u64 * cmp::max(date.year() - reference_year, 
-                                  MIN_YEAR_SPACE) as u64;
-        if separator != '\0' {
-            result *= 4;
-        }
+        // zxcvbn 4.4 counts the years either side of the reference year, dates
+        // in the future are otherwise given the minimum
+        let distance = if config.compat {
+            (date.year() - config.reference_year).abs()
+        } else {
+            date.year() - config.reference_year
+        };
+        let year_space = cmp::max(distance, MIN_YEAR_SPACE) as u64;
+        let separators = if separator != '\0' {
+            4u64
+        } else {
+            1u64
+        };
+        vec![factor("days", 365),
+             factor("year space", year_space),
+             factor("separator", separators)]
+    } else {
+        vec![factor("days", 0)]
     }
-    result
 }
 
 
-fn calculate_average_key_degree(keyboard: &Keyboard) -> f64 {
-    (keyboard.edge_count() as f64) / (keyboard.node_count() as f64)
-}
-
-fn spatial_guesses(m: &BaseMatch) -> u64 {
+fn spatial_factors(m: &BaseMatch) -> Vec<Factor> {
     let mut guesses:u64 = 0;
     let token_length = m.token.chars().count();
     
-    if let MatchData::Spatial{ref graph, ref turns, ref shifted_count} = m.data {
-        let (numkeys, average_degree) = if ["qwerty", "dvorak"].contains(&graph.as_ref()) {
-            (QWERTY_US.node_count(), 
-             calculate_average_key_degree(&*QWERTY_US).round() as u64)
-        } else {
-            (STANDARD_NUMPAD.node_count(), 
-             calculate_average_key_degree(&*STANDARD_NUMPAD).round() as u64)
-        };
-        for i in 2..token_length {
-            let possible_turns = cmp::min(*turns, (i-1)) + 1;
+    if let MatchData::Spatial{ref turns, ref shifted_count, slips, starting_positions,
+        average_degree, ..} = m.data {
+        let numkeys = starting_positions;
+        // Turns are counted over the walk without its slips
+        for i in 2..token_length - slips {
+            let possible_turns = cmp::min(*turns, i - 1) + 1;
             for j in 1..possible_turns {
-                guesses += nCk((i - j) as u64, (j - 1) as u64) * 
-                    numkeys as u64 * average_degree.pow(j as u32);
+                guesses = guesses.saturating_add(
+                    nCk((i - j) as u64, (j - 1) as u64)
+                        .saturating_mul(numkeys as u64)
+                        .saturating_mul(saturating_pow(average_degree, j as u32)));
             }
         }
         let shifted_count = *shifted_count;
         let unshifted_count = token_length - shifted_count;
-        if unshifted_count == 0 || shifted_count == 0 {
-            guesses *= 2;
+        let shifted_variations = if unshifted_count == 0 || shifted_count == 0 {
+            2u64
         } else {
             let temp = cmp::min(unshifted_count, shifted_count)+1;
-            let shifted_variations = (1..temp)
-                .fold(0, |acc, i| acc + 
-                      nCk((shifted_count+unshifted_count) as u64, i as u64));
-            guesses *= shifted_variations;                               
+            (1..temp)
+                .fold(0u64, |acc, i| acc.saturating_add(
+                      nCk((shifted_count+unshifted_count) as u64, i as u64)))
+        };
+        let mut factors = vec![factor("turn variations", guesses),
+                               factor("shift variations", shifted_variations)];
+        if slips > 0 {
+            // Which of the steps slipped, and whether each repeated or skipped
+            // a key
+            let variations = nCk((token_length - 1) as u64, slips as u64)
+                .saturating_mul(saturating_pow(2, slips as u32));
+            factors.push(factor("slip variations", variations));
         }
+        factors
+    } else {
+        vec![factor("turn variations", 0)]
+    }
+}
+
+/// Passwords seen more often are tried sooner, so the guesses fall as the
+/// count rises
+fn breach_factors(m: &BaseMatch) -> Vec<Factor> {
+    if let MatchData::Breach { count } = m.data {
+        vec![factor("breach rank", cmp::max(BREACH_GUESSES / cmp::max(count, 1), 1))]
+    } else {
+        vec![factor("breach rank", 0)]
     }
-    guesses
 }
 
+/// Spatial guesses as zxcvbn 4.4 computes them, counting the characters on
+/// the keyboard as starting positions and using the average degree unrounded.
+/// zxcvbn keeps the fraction which is rounded away here.
+fn zxcvbn_spatial_factors(m: &BaseMatch) -> Vec<Factor> {
+    let token_length = m.token.chars().count();
+    if let MatchData::Spatial{ref graph, turns, shifted_count, ..} = m.data {
+        // zxcvbn's figures for its qwerty graph are used for dvorak too, and
+        // those for the keypad for the Mac keypad
+        let (starting_positions, average_degree) = match graph.as_str() {
+            "qwerty" | "dvorak" => (94.0, 432.0 / 94.0),
+            _ => (15.0, 76.0 / 15.0),
+        };
+        let mut guesses = 0f64;
+        for i in 2..token_length + 1 {
+            let mut degree_power = 1f64;
+            for j in 1..cmp::min(turns, i - 1) + 1 {
+                degree_power *= average_degree;
+                guesses += nCk((i - 1) as u64, (j - 1) as u64) as f64 *
+                           starting_positions * degree_power;
+            }
+        }
+        let unshifted_count = token_length - shifted_count;
+        let shifted_variations = if shifted_count == 0 {
+            1u64
+        } else if unshifted_count == 0 {
+            2u64
+        } else {
+            (1..cmp::min(unshifted_count, shifted_count) + 1)
+                .fold(0u64, |acc, i| acc.saturating_add(
+                      nCk(token_length as u64, i as u64)))
+        };
+        vec![factor("turn variations", round(guesses) as u64),
+             factor("shift variations", shifted_variations)]
+    } else {
+        vec![factor("turn variations", 0)]
+    }
+}
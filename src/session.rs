@@ -0,0 +1,241 @@
+use core::cmp;
+use estimator::Estimator;
+use matching::{self, BaseMatch, GlobalMatches, MatchContext};
+use prelude::*;
+use result::PasswordResult;
+use scoring::{OptimalMatch, ScoringConfig};
+
+/// Incremental estimation for a password being typed. Edits only mark the
+/// characters after them as changed, and the next estimate reuses the matches
+/// and scores for everything before that point.
+///
+/// The local matchers only rescan the changed characters and the longest
+/// match before them. Sequences, years and keyboard walks are scanned again
+/// from the last point before the edit that decides them, and l33t
+/// substitutions are only enumerated again in the regions the edit reaches.
+/// Repeats are matched over the whole password as a new one can start
+/// anywhere before the edit, and breach files and blocklists are read again
+/// whenever the password changes.
+pub struct Session<'a> {
+    estimator: &'a Estimator,
+    password: String,
+    user_inputs: Vec<String>,
+    /// Number of leading characters unchanged since the last estimate
+    unchanged: usize,
+    /// Matches that only depend on the characters they cover
+    local: Vec<BaseMatch>,
+    /// Matches from the last estimate that depend on the whole password
+    global: GlobalMatches,
+    optimal: OptimalMatch,
+    /// Scoring the matches were found and scored with, which includes the year
+    scoring: ScoringConfig,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(estimator: &'a Estimator) -> Session<'a> {
+        let scoring = MatchContext::new(estimator).scoring();
+        Session {
+            estimator,
+            password: String::new(),
+            user_inputs: Vec::new(),
+            unchanged: 0,
+            local: Vec::new(),
+            global: GlobalMatches::default(),
+            optimal: OptimalMatch::new(scoring),
+            scoring,
+        }
+    }
+
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+
+    /// Appends a character to the password
+    pub fn push(&mut self, c: char) {
+        self.password.push(c);
+    }
+
+    /// Appends a string to the password
+    pub fn push_str(&mut self, s: &str) {
+        self.password.push_str(s);
+    }
+
+    /// Removes the last character of the password
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.password.pop();
+        self.unchanged = cmp::min(self.unchanged, self.password.chars().count());
+        c
+    }
+
+    /// Replaces the password, keeping the work done for any common start
+    pub fn set_password(&mut self, password: &str) {
+        let common = self.password
+                         .chars()
+                         .zip(password.chars())
+                         .take_while(|&(a, b)| a == b)
+                         .count();
+        self.unchanged = cmp::min(self.unchanged, common);
+        self.password = password.to_string();
+    }
+
+    /// Sets the words related to the user matched as an extra dictionary, see
+    /// `Estimator::estimate_with_inputs`
+    pub fn set_user_inputs(&mut self, user_inputs: &[&str]) {
+        self.user_inputs = user_inputs.iter().map(|s| s.to_string()).collect();
+        self.unchanged = 0;
+        // l33t regions were matched against the old inputs
+        self.global = GlobalMatches::default();
+    }
+
+    pub fn clear(&mut self) {
+        self.set_password("");
+    }
+
+    /// Estimates the current password, giving the same result as
+    /// `Estimator::estimate_with_inputs`
+    pub fn estimate(&mut self) -> PasswordResult {
+        let estimator = self.estimator;
+        let start = estimator.clock.now();
+        let inputs = self.user_inputs.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+        let context = MatchContext::with_user_inputs(estimator, &inputs);
+        if context.scoring() != self.scoring {
+            // Dates are matched and scored relative to the year
+            self.scoring = context.scoring();
+            self.optimal = OptimalMatch::new(self.scoring);
+            self.unchanged = 0;
+        }
+        let analysed = matching::char_slice(&self.password, 0, estimator.max_length);
+        let length = analysed.chars().count();
+        let unchanged = cmp::min(self.unchanged, length);
+
+        self.local.retain(|m| m.end < unchanged);
+        if unchanged < length {
+            let from = unchanged.saturating_sub(context.longest_local_match() - 1);
+            let window = matching::char_slice(analysed, from, length);
+            for mut m in matching::local_match_with(window, &context) {
+                m.start += from;
+                m.end += from;
+                if m.end >= unchanged {
+                    self.local.push(m);
+                }
+            }
+        }
+
+        let old_global = self.global.matches();
+        self.global.rematch(analysed, unchanged, estimator, &context);
+        let global = self.global.matches();
+        // Scores depend on matches ending at or before their position, except
+        // a match covering the whole password is scored differently to a
+        // submatch so the old last position is always rescored
+        let rescore = cmp::min(first_change(&old_global, &global, unchanged),
+                               unchanged.saturating_sub(1));
+
+        let mut matches = self.local.clone();
+        matches.extend(global);
+        matches.sort();
+        self.optimal.truncate(rescore);
+        self.optimal.score_from(analysed, &matches, rescore);
+        let result = self.optimal.result(analysed.to_string());
+
+        // Matchers were skipped so nothing can be reused next time
+        self.unchanged = if context.timed_out() {
+            0
+        } else {
+            self.password.chars().count()
+        };
+        estimator.finish(result, &matches, &self.password, &context, start)
+    }
+}
+
+fn same_match(a: &BaseMatch, b: &BaseMatch) -> bool {
+    a == b && a.pattern == b.pattern && a.token == b.token && a.data == b.data
+}
+
+/// First position before `limit` where a match present in one list and not
+/// the other ends
+fn first_change(old: &[BaseMatch], new: &[BaseMatch], limit: usize) -> usize {
+    let removed = old.iter().filter(|m| !new.iter().any(|n| same_match(m, n)));
+    let added = new.iter().filter(|m| !old.iter().any(|o| same_match(m, o)));
+    removed.chain(added)
+           .map(|m| m.end)
+           .fold(limit, cmp::min)
+}
+
+
+#[test]
+fn session_matches_estimate_test() {
+    let estimator = Estimator::new();
+    let mut session = estimator.session();
+    for c in "abcd1989qwert".chars() {
+        session.push(c);
+        let result = session.estimate();
+        let expected = estimator.estimate(session.password());
+        assert_eq!(result.guesses, expected.guesses, "{}", session.password());
+        assert_eq!(result.sequence.len(), expected.sequence.len());
+    }
+    while session.pop().is_some() {
+        let result = session.estimate();
+        assert_eq!(result.guesses, estimator.estimate(session.password()).guesses);
+    }
+    session.set_password("password");
+    session.estimate();
+    session.set_password("passw0rd!");
+    let result = session.estimate();
+    assert_eq!(result.guesses, estimator.estimate("passw0rd!").guesses);
+}
+
+#[test]
+fn session_cost_test() {
+    use clock::SteppingClock;
+    use core::time::Duration;
+
+    // The clock is read before each matcher, l33t variant and repeat, so the
+    // readings count the matching work done
+    static CLOCK: SteppingClock = SteppingClock::new();
+    let mut estimator = Estimator::new();
+    estimator.clock = &CLOCK;
+    estimator.time_budget = Some(Duration::from_secs(3600));
+    let mut session = estimator.session();
+    session.push_str("l1ttl3!andalotofwordstypedafterthatwithoutanydigits");
+    session.estimate();
+
+    // Typing one more character leaves the l33t regions before it alone
+    session.push('s');
+    let before = CLOCK.readings();
+    let result = session.estimate();
+    let typed = CLOCK.readings() - before;
+    let before = CLOCK.readings();
+    let expected = estimator.estimate(session.password());
+    assert!(typed < CLOCK.readings() - before);
+    assert_eq!(result.guesses, expected.guesses);
+}
+
+#[test]
+fn session_user_inputs_test() {
+    let estimator = Estimator::new();
+    let mut session = estimator.session();
+    // Longer than any built in word, so the window rescanned must grow
+    let inputs = ["bartholomewfitzwilliams1"];
+    session.set_user_inputs(&inputs);
+    for c in "xbartholomewfitzwilliams1".chars() {
+        session.push(c);
+        let result = session.estimate();
+        let expected = estimator.estimate_with_inputs(session.password(), &inputs);
+        assert_eq!(result.guesses, expected.guesses, "{}", session.password());
+    }
+    assert_eq!(session.estimate().sequence.len(), 2);
+}
+
+#[test]
+fn compat_session_test() {
+    let mut estimator = Estimator::new();
+    estimator.compat = true;
+    let mut session = estimator.session();
+    // Dates typed one digit at a time replace the shorter ones inside them
+    for c in "1/1/1991x2015_06_04".chars() {
+        session.push(c);
+        let result = session.estimate();
+        assert_eq!(result.guesses, estimator.estimate(session.password()).guesses,
+                   "{}", session.password());
+    }
+}
@@ -0,0 +1,81 @@
+use wasm_bindgen::prelude::*;
+use js_sys::{Array, Object, Reflect};
+use estimator::DEFAULT_ESTIMATOR;
+use result::PasswordResult;
+
+/// Estimates the strength of a password with the default estimator. The
+/// returned object follows the layout of the result from dropbox's zxcvbn.js
+/// so it can be swapped in for it.
+#[wasm_bindgen]
+pub fn zxcvbn(password: &str, user_inputs: Option<Box<[JsValue]>>) -> JsValue {
+    let inputs = user_inputs.iter()
+                            .flat_map(|x| x.iter())
+                            .filter_map(input_string)
+                            .collect::<Vec<String>>();
+    let inputs = inputs.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+    to_js(&DEFAULT_ESTIMATOR.estimate_with_inputs(password, &inputs)).into()
+}
+
+/// zxcvbn.js accepts numbers and booleans as user inputs and matches them as
+/// strings
+fn input_string(value: &JsValue) -> Option<String> {
+    value.as_string()
+         .or_else(|| value.as_f64().map(|n| n.to_string()))
+         .or_else(|| value.as_bool().map(|b| b.to_string()))
+}
+
+fn set(object: &Object, key: &str, value: JsValue) {
+    // Defining a property on a plain object can't fail
+    let _ = Reflect::set(object, &JsValue::from_str(key), &value);
+}
+
+fn to_js(result: &PasswordResult) -> Object {
+    let object = Object::new();
+    set(&object, "password", JsValue::from_str(&result.password));
+    set(&object, "guesses", JsValue::from_f64(result.guesses as f64));
+    set(&object, "guesses_log10", JsValue::from_f64(result.guesses_log10));
+    set(&object, "score", match result.score {
+        Some(score) => JsValue::from_f64(score as u8 as f64),
+        None => JsValue::NULL,
+    });
+    set(&object, "calc_time", JsValue::from_f64(result.calculation_time as f64));
+
+    let times = Object::new();
+    let crack_times = &result.crack_times;
+    set(&times, "online_throttling_100_per_hour",
+        JsValue::from_f64(crack_times.online_throttling));
+    set(&times, "online_no_throttling_10_per_second",
+        JsValue::from_f64(crack_times.online_no_throttling));
+    set(&times, "offline_slow_hashing_1e4_per_second",
+        JsValue::from_f64(crack_times.offline_slow_hashing));
+    set(&times, "offline_fast_hashing_1e10_per_second",
+        JsValue::from_f64(crack_times.offline_fast_hashing));
+    set(&object, "crack_times_seconds", times.into());
+
+    let feedback = Object::new();
+    let suggestions = Array::new();
+    if let Some(ref f) = result.feedback {
+        set(&feedback, "warning", JsValue::from_str(&f.advice));
+        for s in f.suggestions.iter() {
+            suggestions.push(&JsValue::from_str(s));
+        }
+    } else {
+        set(&feedback, "warning", JsValue::from_str(""));
+    }
+    set(&feedback, "suggestions", suggestions.into());
+    set(&object, "feedback", feedback.into());
+
+    let sequence = Array::new();
+    for m in result.sequence.iter() {
+        let js_match = Object::new();
+        // zxcvbn.js names its patterns in lower case
+        set(&js_match, "pattern", JsValue::from_str(&m.pattern.to_lowercase()));
+        set(&js_match, "i", JsValue::from_f64(m.start as f64));
+        set(&js_match, "j", JsValue::from_f64(m.end as f64));
+        set(&js_match, "token", JsValue::from_str(&m.token));
+        sequence.push(&js_match.into());
+    }
+    set(&object, "sequence", sequence.into());
+    set(&object, "truncated", JsValue::from_bool(result.truncated));
+    object
+}
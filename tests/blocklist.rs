@@ -0,0 +1,53 @@
+// Run with `cargo test --features blocklist`
+#![cfg(feature = "blocklist")]
+extern crate zxcvbn_rs;
+
+use std::env;
+use std::fs;
+use std::process::Command;
+use zxcvbn_rs::blocklist::Blocklist;
+use zxcvbn_rs::estimator::Estimator;
+
+#[test]
+fn blocklist_tool_test() {
+    let dir = env::temp_dir().join(format!("zxcvbn-blocklist-tool-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let list = dir.join("banned.txt");
+    let filter = dir.join("banned.bloom");
+    let mut words = (0..1000).map(|i| format!("acme-{}", i)).collect::<Vec<String>>();
+    words.push(String::from("Tr0ub4dour&3\r"));
+    words.push(String::new());
+    fs::write(&list, words.join("\n")).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_zxcvbn-blocklist"))
+        .arg(&list)
+        .arg(&filter)
+        .arg("0.0001")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("1001 words"));
+
+    let blocklist = Blocklist::open(&filter, 3).unwrap();
+    assert_eq!(blocklist.entries(), 1001);
+    assert!(blocklist.false_positive_rate() < 0.0002);
+
+    let mut estimator = Estimator::new();
+    let before = estimator.estimate("Tr0ub4dour&3");
+    estimator.blocklist = Some(blocklist);
+    let after = estimator.estimate("Tr0ub4dour&3");
+    // Rank 3 and the capital T
+    assert_eq!(after.guesses, 6);
+    assert!(after.guesses < before.guesses);
+    assert_eq!(after.feedback.unwrap().advice, "This password is on a list of banned passwords");
+    assert_eq!(estimator.estimate("ACME-999").sequence.len(), 1);
+    assert_eq!(estimator.estimate("staple").guesses, Estimator::new().estimate("staple").guesses);
+
+    estimator.compat = true;
+    assert_eq!(estimator.estimate("Tr0ub4dour&3").guesses, {
+        let mut compat = Estimator::new();
+        compat.compat = true;
+        compat.estimate("Tr0ub4dour&3").guesses
+    });
+    fs::remove_dir_all(dir).unwrap();
+}
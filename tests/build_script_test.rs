@@ -1,3 +1,6 @@
+extern crate zxcvbn_rs;
+
+use zxcvbn_rs::dictionary::Dictionary;
 include!(concat!(env!("OUT_DIR"), "/frequency_data.rs"));
 use std::collections::HashSet;
 
@@ -5,16 +8,26 @@ use std::collections::HashSet;
 
 #[test]
 fn no_duplicates_smart() {
-    let dicts = vec![FEMALE_NAMES, MALE_NAMES, SURNAMES, PASSWORDS,
-                     ENGLISH_WIKIPEDIA, US_TV_AND_FILM];
-
     let mut map:HashSet<String> = HashSet::new();
 
-    for d in dicts.iter() {
-        for word in d.to_vec() {
+    // Only the lists whose features are enabled are generated
+    for &(_, d) in DICTIONARIES.iter() {
+        for (word, _) in d.words() {
             assert!(!map.contains(&word.to_string()), 
                     "Failed {} appears more than once", word);
             map.insert(word.to_string());
         }
     }
 }
+
+#[test]
+fn words_are_valid() {
+    for &(name, d) in DICTIONARIES.iter() {
+        assert!(!d.is_empty(), "{} is empty", name);
+        for (word, rank) in d.words() {
+            assert!(!word.is_empty() && rank > 0 && rank <= d.len(), "{}: {}", name, word);
+            assert!(!word.chars().any(|c| c.is_whitespace() || c.is_control()),
+                    "{}: {:?}", name, word);
+        }
+    }
+}
@@ -0,0 +1,11 @@
+// Run with `cargo test --features capi`. The build generates the C header in
+// OUT_DIR, this checks the copy committed for C users is current.
+#![cfg(feature = "capi")]
+
+#[test]
+fn committed_header_is_current() {
+    let generated = concat!(env!("OUT_DIR"), "/zxcvbn.h");
+    assert!(include_str!(concat!(env!("OUT_DIR"), "/zxcvbn.h")) ==
+            include_str!("../include/zxcvbn.h"),
+            "include/zxcvbn.h is out of date, copy {} over it", generated);
+}
@@ -0,0 +1,41 @@
+extern crate zxcvbn_rs;
+
+use zxcvbn_rs::clock::FixedClock;
+use zxcvbn_rs::estimator::Estimator;
+
+/// Year the reference vectors were generated with
+static CLOCK: FixedClock = FixedClock { year: 2017 };
+
+#[test]
+fn zxcvbn_vectors_test() {
+    let mut estimator = Estimator::new();
+    estimator.compat = true;
+    estimator.clock = &CLOCK;
+
+    let vectors = include_str!("fixtures/zxcvbn-4.4.2.tsv");
+    let mut checked = false;
+    for line in vectors.lines().filter(|l| !l.starts_with('#') && !l.is_empty()) {
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        let password = fields[0];
+        let guesses = fields[1].parse::<f64>().unwrap();
+        let score = fields[2].parse::<u8>().unwrap();
+
+        let result = estimator.estimate(password);
+        // zxcvbn keeps the fraction of spatial guesses, which is rounded away
+        assert_eq!(result.guesses, guesses.round() as u64, "guesses for {:?}", password);
+        assert_eq!(result.score.map(|s| s as u8), Some(score), "score for {:?}", password);
+        checked = true;
+    }
+    assert!(checked, "No vectors in the fixture");
+}
+
+#[test]
+fn compat_differs_test() {
+    // Without compat dates in the past are given the minimum year space and
+    // sequences aren't charged the additive term
+    let mut estimator = Estimator::new();
+    estimator.clock = &CLOCK;
+    assert_eq!(estimator.estimate("14/07/1986").guesses, 29200);
+    estimator.compat = true;
+    assert_eq!(estimator.estimate("14/07/1986").guesses, 45261);
+}
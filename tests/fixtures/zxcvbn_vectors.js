@@ -0,0 +1,54 @@
+// Writes zxcvbn-4.4.2.tsv with the guesses and scores dropbox's zxcvbn gives
+// the passwords below. The output is committed as written, add passwords here
+// rather than to the file. Run from this directory:
+//
+//     npm install zxcvbn@4.4.2
+//     node zxcvbn_vectors.js
+
+var fs = require('fs');
+var path = require('path');
+
+// zxcvbn compares dates to the current year, pin it to the one the tests use
+var REFERENCE_YEAR = 2017;
+var scoring = require('zxcvbn/lib/scoring');
+scoring.REFERENCE_YEAR = REFERENCE_YEAR;
+var zxcvbn = require('zxcvbn/lib/main');
+var version = require('zxcvbn/package.json').version;
+
+var passwords = [
+    // Bruteforce and top passwords
+    '', 'password', '123456', 'qwerty', 'iloveyou', '&#',
+    'correcthorsebatterystaple',
+    // Mixed case
+    'Password', 'passworD', 'PASSword', 'PaSsWoRd',
+    // Reversed words
+    'drowssap', 'ytrewq', '321drowssap',
+    // l33t, with one and several substitutions
+    'p4ssw0rd', 'P@ssw0rd!', 'p4$$w0rd', 'tr0ub4d0ur', '4d4m5m1th',
+    // Repeats
+    'jjj', 'aaaaaa', 'abcabcabc', 'aabaabaab', '11111111',
+    // Sequences
+    'abcdef', 'fedcba', '13579', 'ZYXWV',
+    // Spatial walks, unshifted and shifted
+    'zxcvbn', 'ZXCVBN', 'Qwerty', 'qWeRtY', '!@#$%^', 'dfghjk1990',
+    // Dates with separators
+    '1/1/1991', '14/07/1986', '2015_06_04', '31/12/2049', '1.1.91',
+    '91-12-31', '12 31 91',
+    // Dates without separators, four to eight digits
+    '1191', '11191', '111991', '1111991', '13111991', '19911311',
+    // Recent years
+    '1991 2001', '2016'
+];
+
+var lines = [
+    '# Generated by zxcvbn_vectors.js from zxcvbn ' + version + ' with its',
+    '# reference year set to ' + REFERENCE_YEAR + ', do not edit. Checked by',
+    '# tests/compat.rs with Estimator::compat set.',
+    '#',
+    '# Columns are separated by tabs: password, guesses, score.'
+];
+passwords.forEach(function (password) {
+    var result = zxcvbn(password);
+    lines.push([password, result.guesses, result.score].join('\t'));
+});
+fs.writeFileSync(path.join(__dirname, 'zxcvbn-4.4.2.tsv'), lines.join('\n') + '\n');
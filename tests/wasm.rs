@@ -0,0 +1,49 @@
+// Run with `wasm-pack test --node -- --features wasm`
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+extern crate js_sys;
+extern crate wasm_bindgen;
+extern crate wasm_bindgen_test;
+extern crate zxcvbn_rs;
+
+use js_sys::{Array, Reflect};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::*;
+use zxcvbn_rs::estimator::Estimator;
+use zxcvbn_rs::wasm::zxcvbn;
+
+fn get(value: &JsValue, key: &str) -> JsValue {
+    Reflect::get(value, &JsValue::from_str(key)).unwrap()
+}
+
+#[wasm_bindgen_test]
+fn common_password() {
+    let result = zxcvbn("password", None);
+    assert_eq!(get(&result, "score").as_f64(), Some(0.0));
+    assert_eq!(get(&result, "guesses").as_f64(), Some(2.0));
+    let sequence = Array::from(&get(&result, "sequence"));
+    assert_eq!(sequence.length(), 1);
+    assert_eq!(get(&sequence.get(0), "token").as_string().unwrap(), "password");
+    assert_eq!(get(&sequence.get(0), "pattern").as_string().unwrap(), "dictionary");
+    let feedback = get(&result, "feedback");
+    assert_eq!(get(&feedback, "warning").as_string().unwrap(),
+               "This is a top-10 common password");
+}
+
+#[wasm_bindgen_test]
+fn matches_native_estimate() {
+    let password = "jsmith1987!";
+    let inputs = vec![JsValue::from_str("jsmith"), JsValue::from_f64(1987.0)];
+    let result = zxcvbn(password, Some(inputs.into_boxed_slice()));
+    let native = Estimator::new().estimate_with_inputs(password, &["jsmith", "1987"]);
+    assert_eq!(get(&result, "guesses").as_f64(), Some(native.guesses as f64));
+}
+
+#[wasm_bindgen_test]
+fn lower_case_patterns() {
+    let result = zxcvbn("kjhgf97531xyzxyz11/04/1987", None);
+    let sequence = Array::from(&get(&result, "sequence"));
+    let patterns = sequence.iter()
+                           .map(|m| get(&m, "pattern").as_string().unwrap())
+                           .collect::<Vec<String>>();
+    assert_eq!(patterns, ["spatial", "sequence", "repeat", "date"]);
+}